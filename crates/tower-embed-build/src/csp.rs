@@ -0,0 +1,162 @@
+//! Content Security Policy hashes of inline scripts and styles, see
+//! <https://www.w3.org/TR/CSP3/#grammardef-hash-source>.
+
+/// The default policy, allowing the scripts and styles of the same origin and the inline ones of
+/// the page.
+pub const DEFAULT_POLICY: &str = "default-src 'self'; script-src 'self' {script-hashes}; \
+    style-src 'self' {style-hashes}; object-src 'none'; base-uri 'self'";
+
+/// Returns the policy of the HTML page, replacing the `{script-hashes}` and `{style-hashes}`
+/// placeholders with the hash sources of its inline scripts and styles.
+pub fn policy(template: &str, html: &str) -> String {
+    let script_hashes = inline_contents(html, "script")
+        .map(hash_source)
+        .collect::<Vec<_>>();
+    let style_hashes = inline_contents(html, "style")
+        .map(hash_source)
+        .collect::<Vec<_>>();
+
+    template
+        .replace("{script-hashes}", &script_hashes.join(" "))
+        .replace("{style-hashes}", &style_hashes.join(" "))
+        .split(';')
+        .map(|directive| directive.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|directive| !directive.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Returns the hash source of the content, like `'sha256-<base64 digest>'`.
+#[cfg(feature = "csp")]
+fn hash_source(content: &str) -> String {
+    use base64::Engine;
+    use sha2::Digest;
+
+    let digest = sha2::Sha256::digest(content.as_bytes());
+    let digest = base64::engine::general_purpose::STANDARD.encode(digest);
+    format!("'sha256-{digest}'")
+}
+
+#[cfg(not(feature = "csp"))]
+fn hash_source(_content: &str) -> String {
+    unreachable!("the feature is checked when the attribute is parsed")
+}
+
+/// Returns the contents of the elements with the given name, skipping comments and the scripts
+/// loaded with the `src` attribute.
+fn inline_contents<'a>(html: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    // only ASCII characters are lowercased, the offsets are the same of the original text
+    let lowercase = html.to_ascii_lowercase();
+    let mut position = 0;
+    std::iter::from_fn(move || {
+        loop {
+            let start = position + lowercase[position..].find('<')?;
+            let rest = &lowercase[start..];
+            if rest.starts_with("<!--") {
+                position = start + rest.find("-->").map_or(rest.len(), |end| end + 3);
+                continue;
+            }
+
+            let Some(tag) = rest[1..]
+                .strip_prefix(name)
+                .filter(|tag| tag.starts_with(|c: char| c.is_ascii_whitespace() || c == '>'))
+            else {
+                position = start + 1;
+                continue;
+            };
+            let attributes_start = start + 1 + name.len();
+            let attributes_end = attributes_start + tag_end(tag)?;
+            let content_start = attributes_end + 1;
+            let closing = format!("</{name}");
+            let content_end = content_start + lowercase[content_start..].find(&closing)?;
+            position = content_end;
+
+            let attributes = &lowercase[attributes_start..attributes_end];
+            if !has_attribute(attributes, "src") {
+                return Some(&html[content_start..content_end]);
+            }
+        }
+    })
+}
+
+/// Returns the offset of the `>` closing the tag, skipping quoted attribute values.
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    tag.char_indices().find_map(|(offset, c)| {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, '>') => return Some(offset),
+            _ => {}
+        }
+        None
+    })
+}
+
+/// Returns true if the attributes of a tag contain the given name.
+fn has_attribute(attributes: &str, name: &str) -> bool {
+    let mut rest = attributes;
+    while let Some(start) = rest.find(|c: char| !c.is_ascii_whitespace() && c != '/') {
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        if &rest[..end] == name {
+            return true;
+        }
+        rest = rest[end..].trim_start();
+        if let Some(value) = rest.strip_prefix('=') {
+            let value = value.trim_start();
+            rest = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    value[1..].find(quote).map_or("", |end| &value[end + 2..])
+                }
+                _ => value
+                    .find(|c: char| c.is_ascii_whitespace())
+                    .map_or("", |end| &value[end..]),
+            };
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_inline_scripts_and_styles() {
+        let html = "<html><head>\
+            <SCRIPT type=\"module\">alert('Hello, world.');</SCRIPT>\
+            <script src=\"app.js\"></script>\
+            <script data-src='x>y'>console.log(1)</script>\
+            <!-- <script>ignored()</script> -->\
+            <style>body{}</style><stylesheet></stylesheet>\
+            </head></html>";
+        assert_eq!(
+            inline_contents(html, "script").collect::<Vec<_>>(),
+            ["alert('Hello, world.');", "console.log(1)"]
+        );
+        assert_eq!(
+            inline_contents(html, "style").collect::<Vec<_>>(),
+            ["body{}"]
+        );
+        assert_eq!(
+            hash_source("alert('Hello, world.');"),
+            "'sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng='"
+        );
+    }
+
+    #[test]
+    fn policy_without_inline_contents() {
+        assert_eq!(
+            policy(DEFAULT_POLICY, "<script src=\"app.js\"></script>"),
+            "default-src 'self'; script-src 'self'; style-src 'self'; object-src 'none'; \
+             base-uri 'self'"
+        );
+        assert_eq!(
+            policy("script-src {script-hashes}; ", "<style>p{}</style>"),
+            "script-src"
+        );
+    }
+}
@@ -0,0 +1,2587 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use quote::ToTokens;
+use tower_embed_core::{
+    glob::Glob,
+    hasher::{self, Strength},
+    headers, pack, substitute,
+};
+
+use crate::{csp, integrity, last_modified, manifest, rules, storage};
+
+/// Where the generated code is included.
+pub(crate) enum Target {
+    /// The expansion of the derive macro.
+    Derive,
+    /// A file written by a build script in `OUT_DIR`, the build script declares the files to
+    /// rerun on change.
+    BuildScript(PathBuf),
+}
+
+pub(crate) fn expand_derive_embed(
+    input: syn::DeriveInput,
+    target: &Target,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let DeriveEmbed { ident, attrs } = DeriveEmbed::from_ast(&input)?;
+    let key = expansion_key(&input);
+    let DeriveEmbedAttrs {
+        folder,
+        files: single_files,
+        virtual_files,
+        crate_path,
+        index,
+        extension,
+        not_found,
+        headers_file,
+        redirects_file,
+        fingerprint,
+        manifest,
+        manifest_file,
+        integrity,
+        integrity_algorithm,
+        csp,
+        etag,
+        last_modified,
+        storage,
+        packed,
+        substitute,
+    } = attrs;
+
+    let root = root_absolute_path(folder.as_deref().unwrap_or_default());
+    let manifest = manifest
+        .map(|(format, value)| {
+            let path = match manifest_file {
+                Some(file) => file.value(),
+                None => format
+                    .default_paths()
+                    .iter()
+                    .find(|path| root.join(path).is_file())
+                    .map(|path| path.to_string())
+                    .ok_or_else(|| {
+                        syn::Error::new_spanned(
+                            &value,
+                            format_args!(
+                                "manifest not found, expected one of {:?}",
+                                format.default_paths()
+                            ),
+                        )
+                    })?,
+            };
+            Ok::<_, syn::Error>((format, value, path))
+        })
+        .transpose()?;
+
+    let excluded = [&headers_file, &redirects_file]
+        .into_iter()
+        .flatten()
+        .map(|file| file.value())
+        .chain(
+            manifest
+                .iter()
+                .filter(|(format, _, _)| !format.is_served())
+                .map(|(_, _, path)| path.clone()),
+        )
+        .collect::<Vec<_>>();
+    let single_files = get_single_files(&single_files, &virtual_files, &etag)?;
+    // the files declared with `file` take precedence over the ones in the folder
+    let mut files = folder
+        .iter()
+        .flat_map(|folder| get_files(&root, folder, &index, &etag))
+        .filter(|file| !excluded.iter().any(|path| file.relative_path == *path))
+        .filter(|file| {
+            !single_files
+                .iter()
+                .any(|single| single.relative_path == file.relative_path)
+        })
+        .collect::<Vec<_>>();
+    files.extend(single_files);
+    if let Some((glob, value, vars)) = &substitute {
+        substitute_files(&mut files, glob, value, vars, &etag)?;
+    }
+    let fingerprints = files
+        .iter()
+        .filter(|file| matches!(file.kind, FileKind::File))
+        .filter(|file| {
+            fingerprint
+                .as_ref()
+                .is_some_and(|glob| glob.matches(file.relative_path.as_str()))
+        })
+        .filter_map(|file| {
+            let etag = file.etag.as_ref()?;
+            let hash = etag.value();
+            let hash = &hash[..hash.len().min(16)];
+            let path = fingerprinted_path(&file.relative_path, hash);
+            Some((file.relative_path.as_str(), path))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let (last_modified_mode, last_modified) = last_modified;
+    let last_modified_resolver =
+        last_modified::Resolver::new(last_modified_mode, folder.as_ref().map(|_| root.as_path()))
+            .map_err(|err| match &last_modified {
+            Some(value) => syn::Error::new_spanned(value, err),
+            None => syn::Error::new_spanned(&input, err),
+        })?;
+    let resolve_last_modified = |file: &File| {
+        if file.single {
+            last_modified_resolver.resolve_file(&file.absolute_path)
+        } else {
+            last_modified_resolver.resolve(&file.relative_path, &file.absolute_path)
+        }
+    };
+    // rebuild when the environment variable changes
+    let track_source_date_epoch = (last_modified_mode == last_modified::Mode::SourceDateEpoch)
+        .then(|| quote::quote! { const _: Option<&str> = option_env!("SOURCE_DATE_EPOCH"); });
+
+    let virtual_table = virtual_table(
+        &ident,
+        &crate_path,
+        &virtual_files,
+        &etag,
+        last_modified_resolver.resolve_virtual(),
+    );
+    // the virtual files take precedence over the other files
+    let extend_virtual_files = quote::quote! {
+        files.extend(#ident::__virtual_files().iter().map(|(path, content, metadata)| {
+            (*path, Entry::File(content, metadata.clone()))
+        }));
+    };
+
+    // rebuild when the environment variables of the placeholders change
+    let track_vars = substitute
+        .iter()
+        .flat_map(|(_, _, vars)| vars)
+        .filter_map(|var| {
+            let env = var.env.as_ref()?;
+            Some(quote::quote! { const _: &str = env!(#env); })
+        });
+    let track_vars = quote::quote! { #(#track_vars)* };
+    // the debug backend substitutes the placeholders on the fly
+    let (debug_substitute_check, debug_substitute) = substitute
+        .as_ref()
+        .map(|(_, value, vars)| {
+            let names = vars.iter().map(|var| var.name.to_string());
+            let exprs = vars.iter().map(|var| &var.expr);
+            let check = quote::quote! {
+                let substituted = #crate_path::core::glob::Glob::parse(#value)
+                    .is_ok_and(|glob| glob.matches(path));
+            };
+            let substitute = quote::quote! {
+                if substituted {
+                    const VARS: &[(&str, &str)] = &[#((#names, #exprs)),*];
+
+                    let content = #crate_path::file::substitute(&filename, VARS).await?;
+                    return Ok(Embedded {
+                        content: Content::from_bytes(content),
+                        metadata,
+                    });
+                }
+            };
+            (check, substitute)
+        })
+        .unzip();
+
+    let contents = Contents::deduplicate(&files);
+    // procedural macros cannot emit notes on stable, only build scripts report the savings as
+    // warnings of cargo
+    if contents.saved > 0 && matches!(target, Target::BuildScript(_)) {
+        println!(
+            "cargo:warning=`{}` embeds {} files with identical content once, saving {} bytes",
+            ident, contents.duplicates, contents.saved
+        );
+    }
+    let mut compressed_contents = CompressedContents::new(storage, &ident, &key, target);
+    let mut stored_sizes = (0, 0);
+    let mut store = |content: &[u8]| {
+        let compressed = compressed_contents
+            .compress(content)
+            .map_err(|err| syn::Error::new_spanned(&input, err))?;
+        if let Some((_, compressed)) = &compressed {
+            stored_sizes.0 += content.len();
+            stored_sizes.1 += compressed.len();
+        }
+        Ok::<_, syn::Error>(compressed)
+    };
+
+    let files_table = if packed {
+        let mut writer = pack::Writer::new();
+        let ranges = contents
+            .files
+            .iter()
+            .map(|file| {
+                let content = file.content().unwrap_or_default();
+                Ok(match store(&content)? {
+                    Some((_, compressed)) => {
+                        (writer.add_content(&compressed), storage.content_encoding())
+                    }
+                    None => (writer.add_content(&content), None),
+                })
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        for file in &files {
+            let relative_path = file.relative_path.as_str();
+            match &file.kind {
+                FileKind::File => {
+                    let (range, content_encoding) = ranges[contents.indexes[relative_path]];
+                    let metadata = pack::FileMetadata {
+                        etag: file.etag.clone(),
+                        last_modified: resolve_last_modified(file),
+                        immutable: false,
+                        content_encoding,
+                    };
+                    if let Some(path) = fingerprints.get(relative_path) {
+                        let metadata = pack::FileMetadata {
+                            immutable: true,
+                            ..metadata.clone()
+                        };
+                        writer.add_file(path, range, &metadata);
+                    }
+                    writer.add_file(relative_path, range, &metadata);
+                }
+                FileKind::Dir(index) => {
+                    writer.add_redirect(relative_path, index);
+                    writer.add_redirect(&format!("{relative_path}/"), index);
+                }
+            }
+        }
+
+        let archive_path = write_archive(&ident, &key, writer.finish(), target)
+            .map_err(|err| syn::Error::new_spanned(&input, err))?;
+        let tracked_paths = match target {
+            Target::Derive => contents
+                .files
+                .iter()
+                .map(|file| source_path(&file.source))
+                .collect(),
+            Target::BuildScript(_) => Vec::new(),
+        };
+
+        quote::quote! {
+            use #crate_path::core::pack::{self, Entry};
+
+            // rebuild when the files change, only the archive is in the binary
+            #(const _: &[u8] = include_bytes!(#tracked_paths);)*
+
+            static ARCHIVE: &[u8] = include_bytes!(#archive_path);
+
+            static FILES: LazyLock<HashMap<&'static str, Entry>> = LazyLock::new(|| {
+                let mut files = pack::read(ARCHIVE);
+                #extend_virtual_files
+                files
+            });
+        }
+    } else {
+        let content_entries = contents
+            .files
+            .iter()
+            .map(|file| {
+                let path = source_path(&file.source);
+                if storage == storage::Storage::Raw && file.substituted.is_none() {
+                    return Ok(quote::quote! { (include_bytes!(#path), None) });
+                }
+
+                let content = file.content().unwrap_or_default();
+                Ok(match store(&content)? {
+                    Some((compressed_path, _)) => {
+                        let encoding = match storage.content_encoding() {
+                            Some(headers::ContentEncoding::Brotli) => quote::quote! { Brotli },
+                            _ => quote::quote! { Zstd },
+                        };
+                        let compressed_path = include_path(&compressed_path, target)
+                            .map_err(|err| syn::Error::new_spanned(&input, err))?;
+                        quote::quote! {
+                            (
+                                // rebuild when the file changes
+                                {
+                                    const _: &[u8] = include_bytes!(#path);
+                                    include_bytes!(#compressed_path)
+                                },
+                                Some(headers::ContentEncoding::#encoding),
+                            )
+                        }
+                    }
+                    // the substituted content differs from the file
+                    None if file.substituted.is_some() => {
+                        let content = proc_macro2::Literal::byte_string(&content);
+                        quote::quote! {
+                            (
+                                // rebuild when the file changes
+                                { const _: &[u8] = include_bytes!(#path); #content },
+                                None,
+                            )
+                        }
+                    }
+                    None => quote::quote! { (include_bytes!(#path), None) },
+                })
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        let content_count = contents.files.len();
+
+        let embedded_files = files.iter().map(|file| {
+            let last_modified = match resolve_last_modified(file) {
+                Some(secs) => quote::quote! { headers::LastModified::from_unix_timestamp(#secs) },
+                None => quote::quote! { None },
+            };
+
+            let relative_path = file.relative_path.as_str();
+
+            match &file.kind {
+                FileKind::File => {
+                    let etag = match &file.etag {
+                        Some(etag) if etag.is_weak() => {
+                            let etag = etag.value();
+                            quote::quote! { headers::ETag::weak(#etag) }
+                        }
+                        Some(etag) => {
+                            let etag = etag.value();
+                            quote::quote! { headers::ETag::new(#etag) }
+                        }
+                        None => quote::quote! { None },
+                    };
+                    let content_index = contents.indexes[relative_path];
+                    let fingerprinted = fingerprints.get(relative_path).map(|path| {
+                        quote::quote! {
+                            (#path, Entry::File(content, metadata.clone().immutable(true))),
+                        }
+                    });
+
+                    quote::quote! {{
+                        let (content, content_encoding) = CONTENTS[#content_index];
+                        let metadata = Metadata::new(
+                            #crate_path::core::content_type(Path::new(#relative_path)),
+                        )
+                        .etag(#etag)
+                        .last_modified(#last_modified)
+                        .content_encoding(content_encoding);
+                        [#fingerprinted (#relative_path, Entry::File(content, metadata))]
+                    }}
+                }
+                FileKind::Dir(index) => {
+                    let redirect_path = index.as_str();
+                    quote::quote! {{
+                        [
+                            (#relative_path, Entry::Redirect(#redirect_path)),
+                            (concat!(#relative_path, "/"), Entry::Redirect(#redirect_path)),
+                        ]
+                    }}
+                }
+            }
+        });
+
+        quote::quote! {
+            use std::path::Path;
+
+            use #crate_path::core::{Metadata, headers, pack::Entry};
+
+            // files with identical content share the same bytes
+            static CONTENTS: [(&[u8], Option<headers::ContentEncoding>); #content_count] = [#(#content_entries),*];
+
+            static FILES: LazyLock<HashMap<&'static str, Entry>> = LazyLock::new(|| {
+                let mut files = HashMap::new();
+                #(files.extend(#embedded_files);)*
+                #extend_virtual_files
+                files
+            });
+        }
+    };
+
+    compressed_contents.prune();
+    // reported by build scripts only, like the deduplication savings
+    if stored_sizes.0 > 0 && matches!(target, Target::BuildScript(_)) {
+        println!(
+            "cargo:warning=`{}` stores {} bytes compressed in {} bytes, saving {} bytes",
+            ident,
+            stored_sizes.0,
+            stored_sizes.1,
+            stored_sizes.0 - stored_sizes.1
+        );
+    }
+    // fail to compile when the feature decompressing the contents is disabled
+    let require_storage_feature = storage
+        .feature()
+        .map(|feature| quote::quote! { const _: () = #crate_path::__storage::#feature(); });
+
+    let root = root.as_str();
+    let folder = folder.as_deref().map(Path::new);
+    let root_path = match folder {
+        Some(folder) => {
+            let root_path = source_path(folder);
+            quote::quote! { Some(#root_path) }
+        }
+        None => quote::quote! { None },
+    };
+    // the files in the folder are relative to it, the manifest and rules files require it
+    let folder = folder.unwrap_or(Path::new(""));
+
+    let indexes = files.iter().filter_map(|file| match &file.kind {
+        FileKind::Dir(index) => Some((file.relative_path.as_str(), index.as_str())),
+        FileKind::File => None,
+    });
+    let dirs = indexes.clone().map(|(dir, _)| dir);
+    let indexes = indexes.map(|(_, index)| index);
+
+    let file_paths = files
+        .iter()
+        .filter(|file| matches!(file.kind, FileKind::File))
+        .map(|file| file.relative_path.as_str())
+        .chain(virtual_files.iter().map(|file| file.path.as_str()));
+    let clean_urls = clean_urls(file_paths.clone(), &extension);
+    let clean_paths = clean_urls.keys();
+    let clean_urls = clean_urls.values();
+
+    let entry = manifest
+        .map(|(format, value, path)| {
+            let source_path = source_path(&folder.join(&path));
+            let path = Path::new(root).join(path);
+            let entries = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|content| manifest::parse(format, &content))
+                .map_err(|err| {
+                    syn::Error::new_spanned(&value, format_args!("{}: {}", path, err))
+                })?;
+            let sources = entries.keys();
+            let entries = entries.values().map(|entry| {
+                let manifest::Entry { file, css, preload } = entry;
+                quote::quote! {
+                    Entry { file: #file, css: &[#(#css),*], preload: &[#(#preload),*] }
+                }
+            });
+
+            Ok::<_, syn::Error>(quote::quote! {
+                impl #ident {
+                    /// Returns the entry point of the build manifest with the given source path.
+                    pub fn entry(src: &str) -> Option<&'static #crate_path::core::manifest::Entry> {
+                        use std::{collections::HashMap, sync::LazyLock};
+
+                        use #crate_path::core::manifest::Entry;
+
+                        // rebuild when the file changes
+                        const _: &[u8] = include_bytes!(#source_path);
+
+                        static ENTRIES: LazyLock<HashMap<&'static str, Entry>> =
+                            LazyLock::new(|| HashMap::from([#((#sources, #entries)),*]));
+
+                        ENTRIES.get(src)
+                    }
+                }
+            })
+        })
+        .transpose()?;
+
+    let header_rules = headers_file
+        .map(|file| {
+            let content = read_rules_file(Path::new(root), &file)?;
+            let source_path = source_path(&folder.join(file.value()));
+            let rules = rules::parse_headers(&content).map_err(|err| rules_error(&file, err))?;
+            let rules = rules.iter().map(|rule| {
+                let rule_path = rule.path.as_str();
+                let names = rule.headers.iter().map(|(name, _)| name);
+                let values = rule.headers.iter().map(|(_, value)| value);
+                quote::quote! {
+                    HeaderRule { path: #rule_path, headers: &[#((#names, #values)),*] }
+                }
+            });
+
+            Ok::<_, syn::Error>(quote::quote! {
+                fn header_rules() -> &'static [#crate_path::core::rules::HeaderRule] {
+                    use #crate_path::core::rules::HeaderRule;
+
+                    // rebuild when the file changes
+                    const _: &[u8] = include_bytes!(#source_path);
+
+                    &[#(#rules),*]
+                }
+            })
+        })
+        .transpose()?;
+
+    let redirect_rules = redirects_file
+        .map(|file| {
+            let content = read_rules_file(Path::new(root), &file)?;
+            let source_path = source_path(&folder.join(file.value()));
+            let rules = rules::parse_redirects(&content).map_err(|err| rules_error(&file, err))?;
+            let rules = rules.iter().map(|rule| {
+                let rules::RedirectRule {
+                    from,
+                    to,
+                    status,
+                    force,
+                } = rule;
+                quote::quote! {
+                    RedirectRule { from: #from, to: #to, status: #status, force: #force }
+                }
+            });
+
+            Ok::<_, syn::Error>(quote::quote! {
+                fn redirect_rules() -> &'static [#crate_path::core::rules::RedirectRule] {
+                    use #crate_path::core::rules::RedirectRule;
+
+                    // rebuild when the file changes
+                    const _: &[u8] = include_bytes!(#source_path);
+
+                    &[#(#rules),*]
+                }
+            })
+        })
+        .transpose()?;
+
+    let integrity = integrity.map(|glob| {
+        let integrities = files
+            .iter()
+            .filter(|file| matches!(file.kind, FileKind::File))
+            .filter(|file| glob.matches(file.relative_path.as_str()))
+            .filter_map(|file| {
+                let content = file.content().ok()?;
+                let integrity = integrity_algorithm.integrity(&content);
+                let path = file.relative_path.as_str();
+                let fingerprinted = fingerprints.get(path).map(String::as_str);
+                Some(
+                    [Some(path), fingerprinted]
+                        .into_iter()
+                        .flatten()
+                        .map(move |path| (path, integrity.clone())),
+                )
+            })
+            .flatten()
+            .collect::<BTreeMap<_, _>>();
+        let paths = integrities.keys();
+        let integrities = integrities.values();
+
+        quote::quote! {
+            impl #ident {
+                /// Returns the Subresource Integrity metadata of the asset, if it matches the
+                /// `integrity` pattern.
+                #[cfg(not(debug_assertions))]
+                pub fn integrity(
+                    path: impl #crate_path::core::IntoAssetPath<Self>,
+                ) -> Option<&'static str> {
+                    use std::{collections::HashMap, sync::LazyLock};
+
+                    static INTEGRITIES: LazyLock<HashMap<&'static str, &'static str>> =
+                        LazyLock::new(|| HashMap::from([#((#paths, #integrities)),*]));
+
+                    let path = path.into_asset_path();
+                    INTEGRITIES.get(path.trim_start_matches('/')).copied()
+                }
+
+                /// Returns the Subresource Integrity metadata of the asset. In debug builds
+                /// assets can change without recompiling, no metadata is returned.
+                #[cfg(debug_assertions)]
+                pub fn integrity(
+                    path: impl #crate_path::core::IntoAssetPath<Self>,
+                ) -> Option<&'static str> {
+                    let _ = path;
+                    None
+                }
+            }
+        }
+    });
+
+    let content_security_policy = csp.map(|(glob, template)| {
+        let template = template
+            .as_ref()
+            .map_or(csp::DEFAULT_POLICY.to_string(), |value| value.value());
+        let policies = files
+            .iter()
+            .filter(|file| matches!(file.kind, FileKind::File))
+            .filter(|file| glob.matches(file.relative_path.as_str()))
+            .filter_map(|file| {
+                let content = file.content().ok()?;
+                let html = std::str::from_utf8(&content).ok()?;
+                let policy = csp::policy(&template, html);
+                let path = file.relative_path.as_str();
+                let fingerprinted = fingerprints.get(path).map(String::as_str);
+                Some(
+                    [Some(path), fingerprinted]
+                        .into_iter()
+                        .flatten()
+                        .map(move |path| (path, policy.clone())),
+                )
+            })
+            .flatten()
+            .collect::<BTreeMap<_, _>>();
+        let paths = policies.keys();
+        let policies = policies.values();
+
+        quote::quote! {
+            #[cfg(not(debug_assertions))]
+            fn content_security_policy(path: &str) -> Option<&'static str> {
+                use std::{collections::HashMap, sync::LazyLock};
+
+                static POLICIES: LazyLock<HashMap<&'static str, &'static str>> =
+                    LazyLock::new(|| HashMap::from([#((#paths, #policies)),*]));
+
+                let index = <Self as #crate_path::Embed>::index(path);
+                let path = index.as_deref().unwrap_or(path);
+                POLICIES.get(path.trim_start_matches('/')).copied()
+            }
+        }
+    });
+
+    let url = fingerprint.is_some().then(|| {
+        let sources = fingerprints
+            .keys()
+            .flat_map(|path| [path.to_string(), format!("/{path}")]);
+        let targets = fingerprints
+            .values()
+            .flat_map(|path| [path.to_string(), format!("/{path}")]);
+
+        quote::quote! {
+            impl #ident {
+                /// Returns the URL of the asset, fingerprinted if it matches the `fingerprint`
+                /// pattern. The path is returned unchanged if it is not fingerprinted.
+                #[cfg(not(debug_assertions))]
+                pub fn url(
+                    path: impl #crate_path::core::IntoAssetPath<Self>,
+                ) -> std::borrow::Cow<'static, str> {
+                    use std::{borrow::Cow, collections::HashMap, sync::LazyLock};
+
+                    static URLS: LazyLock<HashMap<&'static str, &'static str>> =
+                        LazyLock::new(|| HashMap::from([#((#sources, #targets)),*]));
+
+                    let path = path.into_asset_path();
+                    match URLS.get(path.as_ref()) {
+                        Some(url) => Cow::Borrowed(url),
+                        None => path,
+                    }
+                }
+
+                /// Returns the URL of the asset, fingerprinted if it matches the `fingerprint`
+                /// pattern. In debug builds assets are not fingerprinted, the path is returned
+                /// unchanged.
+                #[cfg(debug_assertions)]
+                pub fn url(
+                    path: impl #crate_path::core::IntoAssetPath<Self>,
+                ) -> std::borrow::Cow<'static, str> {
+                    path.into_asset_path()
+                }
+            }
+        }
+    });
+
+    let single_paths = files
+        .iter()
+        .filter(|file| file.single)
+        .map(|file| file.relative_path.as_str());
+    let single_sources = files
+        .iter()
+        .filter(|file| file.single)
+        .map(|file| source_path(&file.source));
+
+    let asset_files = file_paths.collect::<BTreeSet<_>>();
+    let asset_messages = asset_files
+        .iter()
+        .map(|file| format!("asset not found, did you mean `{file}`?"));
+
+    let nearest_not_found = not_found.map(|not_found| {
+        let pages = nearest_pages(&files, &not_found);
+        let dirs = pages.keys();
+        let pages = pages.values();
+
+        quote::quote! {
+            #[cfg(not(debug_assertions))]
+            fn nearest_not_found(path: &str) -> Option<std::borrow::Cow<'static, str>> {
+                use std::{borrow::Cow, collections::HashMap, sync::LazyLock};
+
+                static PAGES: LazyLock<HashMap<&'static str, &'static str>> =
+                    LazyLock::new(|| HashMap::from([#((#dirs, #pages)),*]));
+
+                let mut path = path.trim_matches('/');
+                loop {
+                    if let Some(page) = PAGES.get(path) {
+                        break Some(Cow::Borrowed(*page));
+                    }
+                    if path.is_empty() {
+                        break None;
+                    }
+                    path = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+                }
+            }
+
+            #[cfg(debug_assertions)]
+            fn nearest_not_found(path: &str) -> Option<std::borrow::Cow<'static, str>> {
+                use std::borrow::Cow;
+
+                let mut path = path.trim_matches('/');
+                loop {
+                    let page = if path.is_empty() {
+                        #not_found.to_string()
+                    } else {
+                        format!("{}/{}", path, #not_found)
+                    };
+                    if Self::__is_file(&page) {
+                        break Some(Cow::Owned(page));
+                    }
+                    if path.is_empty() {
+                        break None;
+                    }
+                    path = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+                }
+            }
+        }
+    });
+
+    // the entity tags of the transformed pages are computed at runtime with the same algorithm
+    let etag_fn = match etag.algorithm {
+        ETagAlgorithm::RapidHash => None,
+        ETagAlgorithm::Sha256 => Some((quote::quote! { sha256 }, quote::quote! { require_sha256 })),
+        ETagAlgorithm::Blake3 => Some((quote::quote! { blake3 }, quote::quote! { require_blake3 })),
+    }
+    .map(|(hash, check)| {
+        quote::quote! {
+            fn etag(
+                content: &[u8],
+                strength: #crate_path::core::hasher::Strength,
+            ) -> #crate_path::core::headers::ETag {
+                const _: () = #crate_path::__etag::#check();
+
+                #crate_path::__etag::#hash(content, strength)
+            }
+        }
+    });
+
+    let expanded = quote::quote! {
+        impl #crate_path::Embed for #ident {
+            #[cfg(not(debug_assertions))]
+            fn get(path: &str) -> impl Future<Output = std::io::Result<#crate_path::core::Embedded>> + Send + 'static {
+                use std::{collections::HashMap, sync::LazyLock};
+
+                use #crate_path::core::{Content, Embedded};
+
+                #track_source_date_epoch
+                #track_vars
+                #require_storage_feature
+
+                #files_table
+
+                let mut path = path;
+                let output = loop {
+                    match FILES.get(path) {
+                        Some(Entry::File(bytes, metadata)) => break Ok(Embedded {
+                            content: Content::from_static(bytes),
+                            metadata: metadata.clone(),
+                        }),
+                        Some(Entry::Redirect(redirect_path)) => {
+                            path = redirect_path;
+                        }
+                        None => break Err(std::io::ErrorKind::NotFound.into()),
+                    };
+                };
+                std::future::ready(output)
+            }
+
+            #[cfg(debug_assertions)]
+            fn get(path: &str) -> impl Future<Output = std::io::Result<#crate_path::core::Embedded>> + Send + 'static {
+                use #crate_path::core::{Content, Embedded, Metadata};
+
+                const EXCLUDED: &[&str] = &[#(#excluded),*];
+
+                let index = <Self as #crate_path::Embed>::index(path);
+                let path = index.as_deref().unwrap_or(path);
+                let virtual_file = Self::__virtual_file(path);
+                let filename = match index {
+                    Some(_) => Self::__source(path),
+                    None if Self::__is_dir(path) => None,
+                    None if EXCLUDED.contains(&path.trim_matches('/')) => None,
+                    None => Self::__source(path),
+                };
+                #debug_substitute_check
+
+                async move {
+                    if let Some((content, metadata)) = virtual_file {
+                        return Ok(Embedded {
+                            content: Content::from_static(content),
+                            metadata,
+                        });
+                    }
+
+                    let Some(filename) = filename else {
+                        return Err(std::io::ErrorKind::NotFound.into());
+                    };
+
+                    let metadata = Metadata::new(#crate_path::core::content_type(&filename));
+
+                    #debug_substitute
+
+                    #crate_path::file::File::open(&filename).await.map(|file| {
+                        Embedded {
+                            content: Content::from_stream(file),
+                            metadata,
+                        }
+                    })
+                }
+            }
+
+            #[cfg(not(debug_assertions))]
+            fn index(path: &str) -> Option<std::borrow::Cow<'static, str>> {
+                use std::{borrow::Cow, collections::HashMap, sync::LazyLock};
+
+                static INDEXES: LazyLock<HashMap<&'static str, &'static str>> =
+                    LazyLock::new(|| HashMap::from([#((#dirs, #indexes)),*]));
+
+                INDEXES
+                    .get(path.trim_matches('/'))
+                    .map(|index| Cow::Borrowed(*index))
+            }
+
+            #[cfg(debug_assertions)]
+            fn index(path: &str) -> Option<std::borrow::Cow<'static, str>> {
+                use std::borrow::Cow;
+
+                let path = path.trim_matches('/');
+                if !Self::__is_dir(path) {
+                    return None;
+                }
+
+                [#(#index),*]
+                    .into_iter()
+                    .map(|index| if path.is_empty() {
+                        index.to_string()
+                    } else {
+                        format!("{}/{}", path, index)
+                    })
+                    .find(|index| Self::__is_file(index))
+                    .map(Cow::Owned)
+            }
+
+            #[cfg(not(debug_assertions))]
+            fn clean_url(path: &str) -> Option<std::borrow::Cow<'static, str>> {
+                use std::{borrow::Cow, collections::HashMap, sync::LazyLock};
+
+                static CLEAN_URLS: LazyLock<HashMap<&'static str, &'static str>> =
+                    LazyLock::new(|| HashMap::from([#((#clean_paths, #clean_urls)),*]));
+
+                CLEAN_URLS.get(path).map(|file| Cow::Borrowed(*file))
+            }
+
+            #[cfg(debug_assertions)]
+            fn clean_url(path: &str) -> Option<std::borrow::Cow<'static, str>> {
+                use std::borrow::Cow;
+
+                if path.is_empty() || path.ends_with('/') {
+                    return None;
+                }
+
+                [#(#extension),*]
+                    .into_iter()
+                    .map(|extension| format!("{}.{}", path, extension))
+                    .find(|file| Self::__is_file(file))
+                    .map(Cow::Owned)
+            }
+
+            #nearest_not_found
+
+            #header_rules
+
+            #redirect_rules
+
+            #content_security_policy
+
+            #etag_fn
+        }
+
+        impl #ident {
+            #[doc(hidden)]
+            pub const fn __asset(path: &'static str) -> #crate_path::core::Asset<Self> {
+                const FILES: &[&str] = &[#(#asset_files),*];
+                const MESSAGES: &[&str] = &[#(#asset_messages),*];
+
+                #crate_path::core::Asset::lookup(path, FILES, MESSAGES)
+            }
+
+            /// Returns the source of the file served at the path, declared with `file` or in the
+            /// folder.
+            #[cfg(debug_assertions)]
+            fn __source(path: &str) -> Option<std::path::PathBuf> {
+                const ROOT: Option<&str> = #root_path;
+                const SOURCES: &[(&str, &str)] = &[#((#single_paths, #single_sources)),*];
+
+                let path = path.trim_matches('/');
+                match SOURCES.iter().find(|(served, _)| *served == path) {
+                    Some((_, source)) => Some(std::path::PathBuf::from(source)),
+                    None => ROOT.map(|root| std::path::Path::new(root).join(path)),
+                }
+            }
+
+            #[cfg(debug_assertions)]
+            fn __is_file(path: &str) -> bool {
+                Self::__virtual_file(path).is_some()
+                    || Self::__source(path).is_some_and(|source| source.is_file())
+            }
+
+            #[cfg(debug_assertions)]
+            fn __virtual_file(path: &str) -> Option<(&'static [u8], #crate_path::core::Metadata)> {
+                let path = path.trim_matches('/');
+                Self::__virtual_files()
+                    .iter()
+                    .find(|(virtual_path, ..)| *virtual_path == path)
+                    .map(|(_, content, metadata)| (*content, metadata.clone()))
+            }
+
+            /// Only the folder has directories, the files declared with `file` are not listed.
+            #[cfg(debug_assertions)]
+            fn __is_dir(path: &str) -> bool {
+                const ROOT: Option<&str> = #root_path;
+
+                ROOT.is_some_and(|root| std::path::Path::new(root).join(path.trim_matches('/')).is_dir())
+            }
+        }
+
+        #virtual_table
+
+        #url
+
+        #entry
+
+        #integrity
+    };
+
+    Ok(expanded)
+}
+
+/// Returns the table of the virtual files, their contents are evaluated by the compiler so the
+/// entity tags are computed on first use.
+fn virtual_table(
+    ident: &syn::Ident,
+    crate_path: &syn::Path,
+    virtual_files: &[VirtualFile],
+    etag: &ETagConfig,
+    last_modified: Option<u64>,
+) -> proc_macro2::TokenStream {
+    let hash = match etag.algorithm {
+        ETagAlgorithm::RapidHash => quote::quote! { rapidhash },
+        ETagAlgorithm::Sha256 => quote::quote! { sha256 },
+        ETagAlgorithm::Blake3 => quote::quote! { blake3 },
+    };
+    // fail to compile when the feature hashing the contents is disabled
+    let require_etag_feature = match etag.algorithm {
+        _ if virtual_files.is_empty() => None,
+        ETagAlgorithm::RapidHash => None,
+        ETagAlgorithm::Sha256 => Some(quote::quote! { require_sha256 }),
+        ETagAlgorithm::Blake3 => Some(quote::quote! { require_blake3 }),
+    }
+    .map(|check| quote::quote! { const _: () = #crate_path::__etag::#check(); });
+    let strength = match etag.strength {
+        Strength::Strong => quote::quote! { Strong },
+        Strength::Weak => quote::quote! { Weak },
+    };
+    // the imports of the entries are unused in an empty table
+    let entry_imports = (!virtual_files.is_empty())
+        .then(|| quote::quote! { use #crate_path::core::{hasher::Strength, headers}; });
+    let last_modified = match last_modified {
+        Some(secs) => quote::quote! { headers::LastModified::from_unix_timestamp(#secs) },
+        None => quote::quote! { None },
+    };
+
+    let entries = virtual_files.iter().map(|file| {
+        let VirtualFile {
+            path,
+            content,
+            content_type,
+        } = file;
+        let content_type = match content_type {
+            Some(content_type) => quote::quote! {
+                headers::ContentType::parse(#content_type).expect("valid content type")
+            },
+            None => quote::quote! {
+                #crate_path::core::content_type(std::path::Path::new(#path))
+            },
+        };
+
+        quote::quote! {{
+            const CONTENT: &str = #content;
+            let content = CONTENT.as_bytes();
+            // assets have no validators in debug builds
+            let metadata = Metadata::new(#content_type)
+                .etag(
+                    (!cfg!(debug_assertions))
+                        .then(|| #crate_path::__etag::#hash(content, Strength::#strength)),
+                )
+                .last_modified(if cfg!(debug_assertions) { None } else { #last_modified });
+            (#path, content, metadata)
+        }}
+    });
+
+    quote::quote! {
+        impl #ident {
+            fn __virtual_files() -> &'static [(&'static str, &'static [u8], #crate_path::core::Metadata)] {
+                use std::sync::LazyLock;
+
+                use #crate_path::core::Metadata;
+                #entry_imports
+
+                #require_etag_feature
+
+                static FILES: LazyLock<Vec<(&'static str, &'static [u8], Metadata)>> =
+                    LazyLock::new(|| vec![#(#entries),*]);
+
+                &FILES
+            }
+        }
+    }
+}
+
+/// Expands the function-like `embed!` macro, the folder is followed by the options of the
+/// `#[embed(...)]` attributes, or it is omitted to embed single files.
+pub(crate) fn expand_embed(
+    input: proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+    use syn::parse::Parser;
+
+    let parser = |input: syn::parse::ParseStream| {
+        let folder = if input.peek(syn::LitStr) {
+            let folder: syn::LitStr = input.parse()?;
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+            Some(folder)
+        } else {
+            None
+        };
+        let attrs: proc_macro2::TokenStream = input.parse()?;
+        Ok((folder, attrs))
+    };
+    let (folder, attrs) = parser.parse2(input)?;
+    let folder = folder.map(|folder| quote::quote! { folder = #folder, });
+
+    let input = syn::parse_quote! {
+        #[embed(#folder #attrs)]
+        struct Assets;
+    };
+    let implementation = expand_derive_embed(input, &Target::Derive)?;
+
+    Ok(quote::quote! {{
+        #[derive(Clone, Copy, Debug, Default)]
+        struct Assets;
+
+        #implementation
+
+        Assets
+    }})
+}
+
+/// A source data annotated with `#[derive(Embed)]``
+struct DeriveEmbed {
+    /// The struct name
+    ident: syn::Ident,
+    /// Attributes of structure
+    attrs: DeriveEmbedAttrs,
+}
+
+/// Attributes for `Embed` derive macro.
+struct DeriveEmbedAttrs {
+    /// The folder to embed
+    folder: Option<String>,
+    /// The single files to embed
+    files: Vec<SingleFile>,
+    /// The files generated from compile-time expressions
+    virtual_files: Vec<VirtualFile>,
+    /// The path to the crate `tower_embed`
+    crate_path: syn::Path,
+    /// The index file names, in order of preference
+    index: Vec<String>,
+    /// The extensions that can be omitted from URLs, in order of preference
+    extension: Vec<String>,
+    /// The not found page file name
+    not_found: Option<String>,
+    /// The file with custom headers, relative to the folder
+    headers_file: Option<syn::LitStr>,
+    /// The file with redirect rules, relative to the folder
+    redirects_file: Option<syn::LitStr>,
+    /// The pattern of files served at fingerprinted paths
+    fingerprint: Option<Glob>,
+    /// The format of the build manifest
+    manifest: Option<(manifest::Format, syn::LitStr)>,
+    /// The build manifest file, relative to the folder
+    manifest_file: Option<syn::LitStr>,
+    /// The pattern of files with Subresource Integrity metadata
+    integrity: Option<Glob>,
+    /// The hash algorithm of Subresource Integrity metadata
+    integrity_algorithm: integrity::Algorithm,
+    /// The pattern of HTML files with a `Content-Security-Policy`, with the policy template
+    csp: Option<(Glob, Option<syn::LitStr>)>,
+    /// The computation of entity tags
+    etag: ETagConfig,
+    /// The source of the modification time of files, with the attribute value
+    last_modified: (last_modified::Mode, Option<syn::Lit>),
+    /// How the contents of the files are stored in the binary
+    storage: storage::Storage,
+    /// Pack the files in a single archive
+    packed: bool,
+    /// The pattern of text files whose placeholders are substituted, with the variables
+    substitute: Option<(Glob, syn::LitStr, Vec<Var>)>,
+}
+
+/// A file declared with `#[embed(file = "...", path = "...")]`.
+struct SingleFile {
+    /// The file, relative to `CARGO_MANIFEST_DIR`
+    file: syn::LitStr,
+    /// The path it is served at, its file name by default
+    path: Option<syn::LitStr>,
+}
+
+/// A variable of `#[embed(vars(NAME = ...))]`, replacing the placeholders.
+struct Var {
+    name: syn::Ident,
+    /// The value, from a literal or an environment variable at compile time
+    value: String,
+    /// The expression of the value, evaluated by the debug backend
+    expr: proc_macro2::TokenStream,
+    /// The environment variable of the value, tracked to rebuild when it changes
+    env: Option<syn::LitStr>,
+}
+
+impl Var {
+    /// Parses `NAME = "value"` or `NAME = env!("VARIABLE")`.
+    fn parse(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Self> {
+        let name = meta
+            .path
+            .get_ident()
+            .cloned()
+            .ok_or_else(|| meta.error("expected the name of a variable"))?;
+        let expr: syn::Expr = meta.value()?.parse()?;
+        match &expr {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(value),
+                ..
+            }) => Ok(Self {
+                name,
+                value: value.value(),
+                expr: expr.to_token_stream(),
+                env: None,
+            }),
+            syn::Expr::Macro(syn::ExprMacro { mac, .. }) if mac.path.is_ident("env") => {
+                let variable: syn::LitStr = mac.parse_body()?;
+                let value = std::env::var(variable.value()).map_err(|_| {
+                    syn::Error::new_spanned(
+                        &variable,
+                        format_args!(
+                            "environment variable `{}` not defined at compile time",
+                            variable.value()
+                        ),
+                    )
+                })?;
+                Ok(Self {
+                    name,
+                    value,
+                    expr: quote::quote! { env!(#variable) },
+                    env: Some(variable),
+                })
+            }
+            _ => Err(syn::Error::new_spanned(
+                expr,
+                "expected a string literal or `env!(...)`",
+            )),
+        }
+    }
+}
+
+/// A file declared with `#[embed(virtual(path = "...", content = ...))]`.
+struct VirtualFile {
+    /// The path it is served at
+    path: String,
+    /// The expression of the content, a `&'static str`
+    content: syn::Expr,
+    /// The media type, guessed from the path by default
+    content_type: Option<String>,
+}
+
+/// The hash algorithm and strength of entity tags.
+#[derive(Clone, Copy, Default)]
+struct ETagConfig {
+    algorithm: ETagAlgorithm,
+    strength: Strength,
+}
+
+#[derive(Clone, Copy, Default)]
+enum ETagAlgorithm {
+    #[default]
+    RapidHash,
+    Sha256,
+    Blake3,
+}
+
+impl ETagConfig {
+    fn compute(&self, content: &[u8]) -> headers::ETag {
+        use tower_embed_core::etag_with;
+
+        match self.algorithm {
+            ETagAlgorithm::RapidHash => etag_with::<hasher::RapidHash>(content, self.strength),
+            #[cfg(feature = "sha256")]
+            ETagAlgorithm::Sha256 => etag_with::<hasher::Sha256>(content, self.strength),
+            #[cfg(feature = "blake3")]
+            ETagAlgorithm::Blake3 => etag_with::<hasher::Blake3>(content, self.strength),
+            #[cfg(not(all(feature = "sha256", feature = "blake3")))]
+            _ => unreachable!("the feature is checked when the algorithm is parsed"),
+        }
+    }
+}
+
+impl VirtualFile {
+    /// Parses the nested `path`, `content` and `content_type` attributes.
+    fn parse(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Self> {
+        let mut path = None;
+        let mut content = None;
+        let mut content_type = None;
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("path") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                let path_value = value.value().trim_matches('/').to_string();
+                if path_value.is_empty() {
+                    return Err(syn::Error::new_spanned(value, "empty path of the file"));
+                }
+                path = Some(path_value);
+            } else if meta.path.is_ident("content") {
+                content = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("content_type") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                if headers::ContentType::parse(&value.value()).is_none() {
+                    return Err(syn::Error::new_spanned(value, "invalid `content_type`"));
+                }
+                content_type = Some(value.value());
+            } else {
+                let name = meta.path.to_token_stream();
+                return Err(syn::Error::new_spanned(
+                    meta.path,
+                    format_args!("unknown `virtual` attribute for `{}`", name),
+                ));
+            }
+            Ok(())
+        })?;
+
+        let (Some(path), Some(content)) = (path, content) else {
+            return Err(meta.error("`virtual` requires `path` and `content` attributes"));
+        };
+        Ok(Self {
+            path,
+            content,
+            content_type,
+        })
+    }
+}
+
+impl DeriveEmbed {
+    fn from_ast(input: &syn::DeriveInput) -> syn::Result<Self> {
+        let syn::Data::Struct(data) = &input.data else {
+            return Err(syn::Error::new_spanned(
+                input,
+                "`Embed` can only be derived for unit structs",
+            ));
+        };
+
+        if !matches!(&data.fields, syn::Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                &data.fields,
+                "`Embed` can only be derived for unit structs",
+            ));
+        }
+
+        let ident = input.ident.clone();
+        let attrs = DeriveEmbedAttrs::from_ast(input)?;
+
+        Ok(Self { ident, attrs })
+    }
+}
+
+impl DeriveEmbedAttrs {
+    fn from_ast(input: &syn::DeriveInput) -> syn::Result<Self> {
+        let mut folder = None;
+        let mut files = Vec::<SingleFile>::new();
+        let mut virtual_files = Vec::new();
+        let mut crate_path = None;
+        let mut index = Vec::new();
+        let mut extension = Vec::new();
+        let mut not_found = None;
+        let mut headers_file = None;
+        let mut redirects_file = None;
+        let mut fingerprint = None;
+        let mut manifest = None;
+        let mut manifest_file = None;
+        let mut integrity = None;
+        let mut integrity_algorithm = None;
+        let mut csp = None;
+        let mut csp_policy = None;
+        let mut etag = ETagConfig::default();
+        let mut last_modified = (last_modified::Mode::default(), None);
+        let mut storage = storage::Storage::default();
+        let mut packed = false;
+        let mut substitute = None;
+        let mut vars = Vec::new();
+
+        for attr in &input.attrs {
+            if !attr.path().is_ident("embed") {
+                continue;
+            }
+
+            let list = attr.meta.require_list()?;
+            if list.tokens.is_empty() {
+                continue;
+            }
+
+            list.parse_nested_meta(|meta| {
+                if meta.path.is_ident("folder") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    folder = Some(value.value());
+                } else if meta.path.is_ident("file") {
+                    let file = meta.value()?.parse()?;
+                    files.push(SingleFile { file, path: None });
+                } else if meta.path.is_ident("virtual") {
+                    let file = VirtualFile::parse(&meta)?;
+                    if virtual_files
+                        .iter()
+                        .any(|other: &VirtualFile| other.path == file.path)
+                    {
+                        return Err(meta.error(format_args!(
+                            "duplicate path `{}` of virtual files",
+                            file.path
+                        )));
+                    }
+                    virtual_files.push(file);
+                } else if meta.path.is_ident("path") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    match files.last_mut() {
+                        Some(file) if file.path.is_none() => file.path = Some(value),
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                value,
+                                "`path` must follow a `file` attribute",
+                            ));
+                        }
+                    }
+                } else if meta.path.is_ident("crate") {
+                    let value: syn::Path = meta.value()?.parse()?;
+                    crate_path = Some(value);
+                } else if meta.path.is_ident("index") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    index.push(value.value());
+                } else if meta.path.is_ident("extension") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    extension.push(value.value());
+                } else if meta.path.is_ident("not_found") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    not_found = Some(value.value());
+                } else if meta.path.is_ident("headers_file") {
+                    headers_file = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("redirects_file") {
+                    redirects_file = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("fingerprint") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    let glob = Glob::parse(&value.value()).map_err(|err| {
+                        syn::Error::new_spanned(&value, format_args!("invalid glob: {}", err))
+                    })?;
+                    fingerprint = Some(glob);
+                } else if meta.path.is_ident("integrity") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    require_feature(cfg!(feature = "integrity"), &value, "integrity", "integrity")?;
+                    let glob = Glob::parse(&value.value()).map_err(|err| {
+                        syn::Error::new_spanned(&value, format_args!("invalid glob: {}", err))
+                    })?;
+                    integrity = Some(glob);
+                } else if meta.path.is_ident("csp") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    require_feature(cfg!(feature = "csp"), &value, "csp", "csp")?;
+                    let glob = Glob::parse(&value.value()).map_err(|err| {
+                        syn::Error::new_spanned(&value, format_args!("invalid glob: {}", err))
+                    })?;
+                    csp = Some(glob);
+                } else if meta.path.is_ident("csp_policy") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    require_feature(cfg!(feature = "csp"), &value, "csp_policy", "csp")?;
+                    let policy = csp::policy(&value.value(), "");
+                    if http::HeaderValue::from_str(&policy).is_err() {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "invalid `csp_policy`, expected a valid header value",
+                        ));
+                    }
+                    csp_policy = Some(value);
+                } else if meta.path.is_ident("etag") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    etag.algorithm = match value.value().as_str() {
+                        "rapidhash" => ETagAlgorithm::RapidHash,
+                        "sha256" => {
+                            let enabled = cfg!(feature = "sha256");
+                            require_feature(enabled, &value, "etag = \"sha256\"", "sha256")?;
+                            ETagAlgorithm::Sha256
+                        }
+                        "blake3" => {
+                            let enabled = cfg!(feature = "blake3");
+                            require_feature(enabled, &value, "etag = \"blake3\"", "blake3")?;
+                            ETagAlgorithm::Blake3
+                        }
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                value,
+                                "unknown `etag` algorithm, expected `rapidhash`, `sha256` or `blake3`",
+                            ));
+                        }
+                    };
+                } else if meta.path.is_ident("last_modified") {
+                    let value: syn::Lit = meta.value()?.parse()?;
+                    last_modified = (last_modified::Mode::parse(&value)?, Some(value));
+                } else if meta.path.is_ident("etag_strength") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    etag.strength = match value.value().as_str() {
+                        "strong" => Strength::Strong,
+                        "weak" => Strength::Weak,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                value,
+                                "unknown `etag_strength`, expected `strong` or `weak`",
+                            ));
+                        }
+                    };
+                } else if meta.path.is_ident("integrity_algorithm") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    let enabled = cfg!(feature = "integrity");
+                    require_feature(enabled, &value, "integrity_algorithm", "integrity")?;
+                    let algorithm = integrity::Algorithm::parse(&value.value())
+                        .map_err(|err| syn::Error::new_spanned(&value, err))?;
+                    integrity_algorithm = Some((algorithm, value));
+                } else if meta.path.is_ident("manifest") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    require_feature(cfg!(feature = "manifest"), &value, "manifest", "manifest")?;
+                    let format = manifest::Format::parse(&value.value())
+                        .map_err(|err| syn::Error::new_spanned(&value, err))?;
+                    manifest = Some((format, value));
+                } else if meta.path.is_ident("storage") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    storage = storage::Storage::parse(&value.value())
+                        .map_err(|err| syn::Error::new_spanned(&value, err))?;
+                    if let Some(feature) = storage.feature() {
+                        let attribute = format!("storage = \"{feature}\"");
+                        let feature = feature.to_string();
+                        require_feature(storage.is_enabled(), &value, &attribute, &feature)?;
+                    }
+                } else if meta.path.is_ident("substitute") {
+                    let value: syn::LitStr = meta.value()?.parse()?;
+                    let glob = Glob::parse(&value.value()).map_err(|err| {
+                        syn::Error::new_spanned(&value, format_args!("invalid glob: {}", err))
+                    })?;
+                    substitute = Some((glob, value));
+                } else if meta.path.is_ident("vars") {
+                    meta.parse_nested_meta(|meta| {
+                        vars.push(Var::parse(&meta)?);
+                        Ok(())
+                    })?;
+                } else if meta.path.is_ident("packed") {
+                    let value: syn::LitBool = meta.value()?.parse()?;
+                    packed = value.value();
+                } else if meta.path.is_ident("manifest_file") {
+                    manifest_file = Some(meta.value()?.parse()?);
+                } else {
+                    let name = meta.path.to_token_stream();
+                    return Err(syn::Error::new_spanned(
+                        meta.path,
+                        format_args!("unknown `embed` attribute for `{}`", name),
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+
+        if folder.is_none() && files.is_empty() && virtual_files.is_empty() {
+            return Err(syn::Error::new_spanned(
+                input,
+                "#[derive(Embed)] requires `folder`, `file` or `virtual` attribute",
+            ));
+        }
+
+        // the rules files and the build manifest are relative to the folder
+        if folder.is_none()
+            && let Some(value) = [&headers_file, &redirects_file, &manifest_file]
+                .into_iter()
+                .flatten()
+                .chain(manifest.as_ref().map(|(_, value)| value))
+                .next()
+        {
+            return Err(syn::Error::new_spanned(
+                value,
+                "a file of the folder requires `folder` attribute",
+            ));
+        }
+
+        if let Some(manifest_file) = &manifest_file
+            && manifest.is_none()
+        {
+            return Err(syn::Error::new_spanned(
+                manifest_file,
+                "`manifest_file` requires `manifest` attribute",
+            ));
+        }
+
+        if let Some((_, value)) = &integrity_algorithm
+            && integrity.is_none()
+        {
+            return Err(syn::Error::new_spanned(
+                value,
+                "`integrity_algorithm` requires `integrity` attribute",
+            ));
+        }
+        let integrity_algorithm = integrity_algorithm
+            .map(|(algorithm, _)| algorithm)
+            .unwrap_or_default();
+
+        if let Some(value) = &csp_policy
+            && csp.is_none()
+        {
+            return Err(syn::Error::new_spanned(
+                value,
+                "`csp_policy` requires `csp` attribute",
+            ));
+        }
+        let csp = csp.map(|glob| (glob, csp_policy));
+
+        if let Some(var) = vars.first()
+            && substitute.is_none()
+        {
+            return Err(syn::Error::new_spanned(
+                &var.name,
+                "`vars` requires `substitute` attribute",
+            ));
+        }
+        let substitute = substitute.map(|(glob, value)| (glob, value, vars));
+
+        let crate_path = crate_path.unwrap_or_else(|| syn::parse_quote! { tower_embed });
+        if index.is_empty() {
+            index.push("index.html".to_string());
+        }
+        if extension.is_empty() {
+            extension.push("html".to_string());
+        }
+
+        Ok(Self {
+            folder,
+            files,
+            virtual_files,
+            crate_path,
+            index,
+            extension,
+            not_found,
+            headers_file,
+            redirects_file,
+            fingerprint,
+            manifest,
+            manifest_file,
+            integrity,
+            integrity_algorithm,
+            csp,
+            etag,
+            last_modified,
+            storage,
+            packed,
+            substitute,
+        })
+    }
+}
+
+/// Returns an error if the feature of `tower-embed` required by the attribute is not enabled.
+fn require_feature(
+    enabled: bool,
+    value: &syn::LitStr,
+    attribute: &str,
+    feature: &str,
+) -> syn::Result<()> {
+    if enabled {
+        return Ok(());
+    }
+    Err(syn::Error::new_spanned(
+        value,
+        format_args!("`{attribute}` requires the `{feature}` feature of `tower-embed`"),
+    ))
+}
+
+fn root_absolute_path(folder: &str) -> PathBuf {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .expect("missing CARGO_MANIFEST_DIR environment variable");
+
+    Path::new(&manifest_dir).join(folder)
+}
+
+/// Returns the expression of the path of a source file, relative to `CARGO_MANIFEST_DIR` so that
+/// the generated code does not depend on the location of the sources.
+fn source_path(path: &Path) -> proc_macro2::TokenStream {
+    if path.is_absolute() {
+        let path = path.as_str();
+        quote::quote! { #path }
+    } else {
+        let path = format!("/{path}");
+        quote::quote! { concat!(env!("CARGO_MANIFEST_DIR"), #path) }
+    }
+}
+
+/// Writes the archive of the packed files in the target directory, or in `OUT_DIR` for build
+/// scripts, returning the expression of its path.
+///
+/// In the target directory the name of the archive contains its hash, an existing archive is not
+/// written again. The archives of the previous expansions with the same key are removed.
+fn write_archive(
+    ident: &syn::Ident,
+    key: &str,
+    archive: Vec<u8>,
+    target: &Target,
+) -> Result<proc_macro2::TokenStream, String> {
+    use hasher::ETagHasher;
+
+    let prefix = format!("{}-{}", output_name(ident, target), key);
+    let name = match target {
+        Target::Derive => format!("{}-{}.pack", prefix, hasher::RapidHash::hash(&archive)),
+        // the build script writes the archive along with the generated code
+        Target::BuildScript(_) => format!("{}.pack", prefix),
+    };
+    let dir = output_dir(target);
+    let path = dir.join(&name);
+    if matches!(target, Target::BuildScript(_)) || !path.is_file() {
+        write_file(&path, &archive)?;
+    }
+    let include_path = include_path(&path, target)?;
+
+    // remove the archives of the previous expansions of the same implementation
+    if let Target::Derive = target
+        && let Ok(entries) = std::fs::read_dir(&dir)
+    {
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            let is_stale = file_name != name
+                && file_name
+                    .strip_prefix(&prefix)
+                    .and_then(|name| name.strip_prefix('-'))
+                    .and_then(|hash| hash.strip_suffix(".pack"))
+                    .is_some_and(|hash| hash.chars().all(|c| c.is_ascii_hexdigit()));
+            if is_stale {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+    Ok(include_path)
+}
+
+/// The contents compressed for the storage, cached in files named by the hash of the content, so
+/// that each content is compressed once and included from a file instead of the generated code.
+///
+/// Each implementation has its own directory, named by the key of the expansion.
+struct CompressedContents {
+    storage: storage::Storage,
+    dir: PathBuf,
+    used: BTreeSet<String>,
+}
+
+impl CompressedContents {
+    fn new(storage: storage::Storage, ident: &syn::Ident, key: &str, target: &Target) -> Self {
+        let name = format!("{}-{}-storage", output_name(ident, target), key);
+        Self {
+            storage,
+            dir: output_dir(target).join(name),
+            used: BTreeSet::new(),
+        }
+    }
+
+    /// Returns the path and the compressed content, `None` if the content is stored raw.
+    fn compress(&mut self, content: &[u8]) -> Result<Option<(PathBuf, Vec<u8>)>, String> {
+        use hasher::ETagHasher;
+
+        let Some(encoding) = self.storage.content_encoding() else {
+            return Ok(None);
+        };
+        let extension = match encoding {
+            headers::ContentEncoding::Brotli => "br",
+            headers::ContentEncoding::Zstd => "zst",
+        };
+        let name = format!(
+            "{}-{}.{}",
+            hasher::RapidHash::hash(content),
+            content.len(),
+            extension
+        );
+        let path = self.dir.join(&name);
+        self.used.insert(name);
+
+        // an empty file marks a content that does not shrink
+        let compressed = match std::fs::read(&path) {
+            Ok(compressed) => compressed,
+            Err(_) => {
+                let compressed = self.storage.compress(content).unwrap_or_default();
+                write_file(&path, &compressed)?;
+                compressed
+            }
+        };
+        Ok((!compressed.is_empty()).then_some((path, compressed)))
+    }
+
+    /// Removes the cached contents of the previous expansions not used anymore.
+    fn prune(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            if name.to_str().is_none_or(|name| !self.used.contains(name)) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Returns the directory of the generated files, shared by the packages of the target directory
+/// for the derive macro.
+fn output_dir(target: &Target) -> PathBuf {
+    match target {
+        Target::Derive => {
+            let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+                .expect("missing CARGO_MANIFEST_DIR environment variable");
+            target_dir(Path::new(&manifest_dir)).join("tower-embed")
+        }
+        Target::BuildScript(out_dir) => out_dir.clone(),
+    }
+}
+
+/// Returns the prefix of the names of the generated files of the implementation.
+fn output_name(ident: &syn::Ident, target: &Target) -> String {
+    match target {
+        Target::Derive => {
+            let package = std::env::var("CARGO_PKG_NAME").unwrap_or_default();
+            format!("{}-{}", package, ident)
+        }
+        Target::BuildScript(_) => ident.to_string(),
+    }
+}
+
+/// Returns the key of the expansion, distinguishing the implementations of the same package with
+/// the same name, like the ones of `embed!`, in the names of the generated files.
+///
+/// The key is the hash of the name and the `#[embed(...)]` attributes, the files of an
+/// implementation are kept when the files of the folder change.
+fn expansion_key(input: &syn::DeriveInput) -> String {
+    use hasher::ETagHasher;
+
+    let attrs = input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("embed"));
+    let ident = &input.ident;
+    let tokens = quote::quote! { #(#attrs)* #ident };
+    hasher::RapidHash::hash(tokens.to_string().as_bytes())
+}
+
+/// Writes a generated file, concurrent builds may write the same file.
+fn write_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    let dir = path.parent().unwrap_or(path);
+    std::fs::create_dir_all(dir).map_err(|err| format!("failed to create `{}`: {}", dir, err))?;
+    let temp_path = PathBuf::from(format!("{}.{}", path, std::process::id()));
+    std::fs::write(&temp_path, content)
+        .and_then(|_| std::fs::rename(&temp_path, path))
+        .map_err(|err| format!("failed to write `{}`: {}", path, err))
+}
+
+/// Returns the expression of the path of a generated file, to be included with `include_bytes!`.
+fn include_path(path: &Path, target: &Target) -> Result<proc_macro2::TokenStream, String> {
+    if let Target::BuildScript(out_dir) = target {
+        let path = path.strip_prefix(out_dir).unwrap_or(path);
+        let path = format!("/{path}");
+        return Ok(quote::quote! { concat!(env!("OUT_DIR"), #path) });
+    }
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+        .expect("missing CARGO_MANIFEST_DIR environment variable");
+    // an absolute path would make the generated code depend on the machine
+    let path = relative_path(Path::new(&manifest_dir), path).ok_or_else(|| {
+        format!(
+            "`{}` is not relative to `CARGO_MANIFEST_DIR`, set `CARGO_TARGET_DIR` to a \
+             directory on the same root, or generate the implementation with a build script",
+            path
+        )
+    })?;
+    let path = format!("/{path}");
+    Ok(quote::quote! { concat!(env!("CARGO_MANIFEST_DIR"), #path) })
+}
+
+/// Returns the target directory of the build, from `CARGO_TARGET_DIR` or the closest `target`
+/// directory created by cargo.
+fn target_dir(manifest_dir: &Path) -> PathBuf {
+    if let Some(dir) = std::env::var_os("CARGO_TARGET_DIR")
+        && let Ok(dir) = std::path::absolute(dir)
+        && let Ok(dir) = PathBuf::try_from(dir)
+    {
+        return dir;
+    }
+
+    manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("target"))
+        .find(|dir| dir.join("CACHEDIR.TAG").is_file())
+        .unwrap_or_else(|| manifest_dir.join("target"))
+}
+
+/// Returns the path relative to the base, if they share a prefix other than the root.
+fn relative_path(base: &Path, path: &Path) -> Option<PathBuf> {
+    let mut base = base.components().peekable();
+    let mut path = path.components().peekable();
+    let mut common = 0;
+    while let (Some(a), Some(b)) = (base.peek(), path.peek())
+        && a == b
+    {
+        base.next();
+        path.next();
+        common += 1;
+    }
+    if common <= 1 {
+        return None;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in base {
+        relative.push("..");
+    }
+    relative.extend(path);
+    Some(relative)
+}
+
+/// Reads a rules file in the embedded folder.
+fn read_rules_file(root: &Path, file: &syn::LitStr) -> syn::Result<String> {
+    let path = root.join(file.value());
+    std::fs::read_to_string(&path).map_err(|err| {
+        syn::Error::new_spanned(file, format_args!("failed to read `{}`: {}", path, err))
+    })
+}
+
+fn rules_error(file: &syn::LitStr, err: rules::Error) -> syn::Error {
+    syn::Error::new_spanned(
+        file,
+        format_args!("{}:{}: {}", file.value(), err.line, err.message),
+    )
+}
+
+fn get_files(
+    root: &Path,
+    folder: &str,
+    index: &[String],
+    etag_config: &ETagConfig,
+) -> impl Iterator<Item = File> {
+    walkdir::WalkDir::new(root)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(move |entry| {
+            let absolute_path: &Path = entry.path().try_into().unwrap();
+            let absolute_path = absolute_path.to_path_buf();
+
+            let relative_path = absolute_path
+                .canonicalize_utf8()
+                .unwrap()
+                .strip_prefix(root)
+                .unwrap()
+                .to_path_buf();
+
+            let mut etag = None;
+            let kind = if entry.file_type().is_file() {
+                etag = std::fs::read(&absolute_path)
+                    .ok()
+                    .map(|content| etag_config.compute(&content));
+                FileKind::File
+            } else if entry.file_type().is_dir() {
+                let index = index
+                    .iter()
+                    .find(|index| absolute_path.join(index).is_file())?;
+
+                FileKind::Dir(relative_path.join(index).into_string())
+            } else {
+                return None;
+            };
+
+            Some(File {
+                kind,
+                source: Path::new(folder).join(&relative_path),
+                relative_path,
+                absolute_path,
+                etag,
+                single: false,
+                substituted: None,
+            })
+        })
+}
+
+/// Substitutes the placeholders of the text files matching the pattern, before they are hashed.
+fn substitute_files(
+    files: &mut [File],
+    glob: &Glob,
+    value: &syn::LitStr,
+    vars: &[Var],
+    etag_config: &ETagConfig,
+) -> syn::Result<()> {
+    let vars = vars
+        .iter()
+        .map(|var| (var.name.to_string(), var.value.as_str()))
+        .collect::<Vec<_>>();
+    let vars = vars
+        .iter()
+        .map(|(name, value)| (name.as_str(), *value))
+        .collect::<Vec<_>>();
+
+    let files = files
+        .iter_mut()
+        .filter(|file| matches!(file.kind, FileKind::File))
+        .filter(|file| glob.matches(file.relative_path.as_str()));
+    for file in files {
+        let error = |message: String| {
+            syn::Error::new_spanned(value, format_args!("{}: {}", file.relative_path, message))
+        };
+        let content =
+            std::fs::read_to_string(&file.absolute_path).map_err(|err| error(err.to_string()))?;
+        let content = substitute::substitute(&content, &vars)
+            .map_err(|err| error(err.to_string()))?
+            .into_bytes();
+        file.etag = Some(etag_config.compute(&content));
+        file.substituted = Some(content);
+    }
+    Ok(())
+}
+
+/// Returns the files declared with `file`, failing if one cannot be read.
+/// Reads the single files, the paths they are served at must be unique.
+fn get_single_files(
+    files: &[SingleFile],
+    virtual_files: &[VirtualFile],
+    etag_config: &ETagConfig,
+) -> syn::Result<Vec<File>> {
+    let mut paths = BTreeSet::new();
+    files
+        .iter()
+        .map(|SingleFile { file, path }| {
+            let source = PathBuf::from(file.value());
+            let absolute_path = root_absolute_path(source.as_str());
+            let content = std::fs::read(&absolute_path).map_err(|err| {
+                syn::Error::new_spanned(
+                    file,
+                    format_args!("failed to read `{}`: {}", absolute_path, err),
+                )
+            })?;
+
+            let relative_path = match path {
+                Some(path) => PathBuf::from(path.value().trim_matches('/')),
+                None => PathBuf::from(source.file_name().unwrap_or_default()),
+            };
+            let span = path.as_ref().unwrap_or(file);
+            if relative_path.as_str().is_empty() {
+                return Err(syn::Error::new_spanned(span, "empty path of the file"));
+            }
+            if virtual_files
+                .iter()
+                .any(|virtual_file| virtual_file.path == relative_path.as_str())
+            {
+                return Err(syn::Error::new_spanned(
+                    span,
+                    format_args!("path `{}` is also served by a virtual file", relative_path),
+                ));
+            }
+            if !paths.insert(relative_path.clone()) {
+                return Err(syn::Error::new_spanned(
+                    span,
+                    format_args!("duplicate path `{}` of single files", relative_path),
+                ));
+            }
+
+            Ok(File {
+                kind: FileKind::File,
+                relative_path,
+                absolute_path,
+                source,
+                etag: Some(etag_config.compute(&content)),
+                single: true,
+                substituted: None,
+            })
+        })
+        .collect()
+}
+
+/// The unique contents of the embedded files.
+struct Contents<'a> {
+    /// A file of each unique content
+    files: Vec<&'a File>,
+    /// The index of the content of each file, by relative path
+    indexes: BTreeMap<&'a str, usize>,
+    /// The number of files sharing the content of another file
+    duplicates: usize,
+    /// The bytes saved by sharing contents
+    saved: u64,
+}
+
+impl<'a> Contents<'a> {
+    /// Groups the files by content, comparing the bytes of files with the same hash.
+    fn deduplicate(files: &'a [File]) -> Self {
+        use hasher::ETagHasher;
+
+        let mut contents = Self {
+            files: Vec::new(),
+            indexes: BTreeMap::new(),
+            duplicates: 0,
+            saved: 0,
+        };
+
+        let mut by_hash = BTreeMap::<String, Vec<usize>>::new();
+        let files = files
+            .iter()
+            .filter(|file| matches!(file.kind, FileKind::File));
+        for file in files {
+            // unreadable files are reported by `include_bytes!`
+            let Ok(content) = file.content() else {
+                contents.insert(file);
+                continue;
+            };
+
+            let candidates = by_hash
+                .entry(hasher::RapidHash::hash(&content))
+                .or_default();
+            let duplicate = candidates.iter().copied().find(|index| {
+                contents.files[*index]
+                    .content()
+                    .is_ok_and(|other| other == content)
+            });
+            match duplicate {
+                Some(index) => {
+                    contents.duplicates += 1;
+                    contents.saved += content.len() as u64;
+                    contents.indexes.insert(file.relative_path.as_str(), index);
+                }
+                None => candidates.push(contents.insert(file)),
+            }
+        }
+        contents
+    }
+
+    /// Inserts a file with a new content, returning the index of the content.
+    fn insert(&mut self, file: &'a File) -> usize {
+        let index = self.files.len();
+        self.files.push(file);
+        self.indexes.insert(file.relative_path.as_str(), index);
+        index
+    }
+}
+
+/// Maps the URLs without extension to the files they serve.
+fn clean_urls<'a>(
+    files: impl Iterator<Item = &'a str> + Clone,
+    extensions: &[String],
+) -> BTreeMap<String, String> {
+    let mut clean_urls = BTreeMap::new();
+    for extension in extensions {
+        for file in files.clone().map(Path::new) {
+            if file.extension() != Some(extension.as_str()) {
+                continue;
+            }
+
+            let clean_path = file.with_extension("");
+            clean_urls
+                .entry(clean_path.into_string())
+                .or_insert_with(|| file.to_string());
+        }
+    }
+    clean_urls
+}
+
+/// Inserts the hash in the file name, before the extension.
+fn fingerprinted_path(path: &Path, hash: &str) -> String {
+    let name = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => format!("{stem}.{hash}.{extension}"),
+        _ => format!("{}.{hash}", path.file_name().unwrap_or_default()),
+    };
+    path.with_file_name(name).into_string()
+}
+
+/// Maps each directory to the closest page with the given name, searching in ancestors.
+fn nearest_pages(files: &[File], name: &str) -> BTreeMap<String, String> {
+    let files = files
+        .iter()
+        .filter(|file| matches!(file.kind, FileKind::File))
+        .map(|file| file.relative_path.as_path())
+        .collect::<BTreeSet<_>>();
+    let dirs = files
+        .iter()
+        .flat_map(|path| path.ancestors().skip(1))
+        .collect::<BTreeSet<_>>();
+
+    // parent directories precede their children in lexicographic order
+    let mut pages = BTreeMap::new();
+    for dir in dirs {
+        let candidate = dir.join(name);
+        let page = if files.contains(candidate.as_path()) {
+            Some(candidate.into_string())
+        } else {
+            dir.parent()
+                .and_then(|parent| pages.get(parent.as_str()).cloned())
+        };
+        if let Some(page) = page {
+            pages.insert(dir.to_string(), page);
+        }
+    }
+    pages
+}
+
+struct File {
+    kind: FileKind,
+    /// The path the file is served at
+    relative_path: PathBuf,
+    absolute_path: PathBuf,
+    /// The path of the file, relative to `CARGO_MANIFEST_DIR` unless absolute
+    source: PathBuf,
+    /// Declared with `file`, instead of found in the folder
+    single: bool,
+    /// The content hash, computed at compile time
+    etag: Option<headers::ETag>,
+    /// The content with the placeholders substituted
+    substituted: Option<Vec<u8>>,
+}
+
+impl File {
+    /// Returns the content embedded for the file.
+    fn content(&self) -> std::io::Result<Cow<'_, [u8]>> {
+        match &self.substituted {
+            Some(content) => Ok(Cow::Borrowed(content)),
+            None => std::fs::read(&self.absolute_path).map(Cow::Owned),
+        }
+    }
+}
+
+enum FileKind {
+    File,
+    /// A directory, with the relative path of its index file
+    Dir(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempDir;
+
+    #[test]
+    fn no_absolute_paths_in_expansion() {
+        let input = syn::parse_quote! {
+            #[embed(folder = "src", not_found = "lib.rs", fingerprint = "*.rs")]
+            struct Assets;
+        };
+        let expanded = expand_derive_embed(input, &Target::Derive)
+            .unwrap()
+            .to_string();
+
+        assert!(expanded.contains("CARGO_MANIFEST_DIR"));
+        assert!(!expanded.contains(env!("CARGO_MANIFEST_DIR")));
+    }
+
+    #[test]
+    fn packed_expansion() {
+        let input = syn::parse_quote! {
+            #[embed(folder = "src", packed = true)]
+            struct Assets;
+        };
+        let expanded = expand_derive_embed(input, &Target::Derive)
+            .unwrap()
+            .to_string();
+
+        assert!(expanded.contains("static ARCHIVE"));
+        assert!(!expanded.contains("static CONTENTS"));
+        assert!(!expanded.contains("fn etag"));
+        assert!(!expanded.contains(env!("CARGO_MANIFEST_DIR")));
+    }
+
+    #[test]
+    fn single_files() {
+        let input = syn::parse_quote! {
+            #[embed(file = "Cargo.toml", path = "/config/cargo.toml", file = "src/lib.rs")]
+            struct Assets;
+        };
+        let expanded = expand_derive_embed(input, &Target::Derive)
+            .unwrap()
+            .to_string();
+
+        assert!(expanded.contains("\"config/cargo.toml\""));
+        assert!(expanded.contains("\"lib.rs\""));
+        assert!(expanded.contains("\"/Cargo.toml\""));
+        assert!(!expanded.contains(env!("CARGO_MANIFEST_DIR")));
+    }
+
+    #[test]
+    fn invalid_single_files() {
+        let error = |input| match expand_derive_embed(input, &Target::Derive) {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.to_string(),
+        };
+
+        let message = error(syn::parse_quote! {
+            #[embed(folder = "src", path = "lib.rs")]
+            struct Assets;
+        });
+        assert!(message.contains("`path` must follow a `file` attribute"));
+
+        let message = error(syn::parse_quote! {
+            #[embed(file = "missing.txt")]
+            struct Assets;
+        });
+        assert!(message.contains("failed to read"));
+
+        let message = error(syn::parse_quote! {
+            #[embed(file = "Cargo.toml", headers_file = "_headers")]
+            struct Assets;
+        });
+        assert!(message.contains("requires `folder` attribute"));
+    }
+
+    #[test]
+    fn content_security_policy() {
+        let input = syn::parse_quote! {
+            #[embed(file = "src/csp.rs", csp = "*.rs", csp_policy = "script-src {script-hashes}")]
+            struct Assets;
+        };
+        let expanded = expand_derive_embed(input, &Target::Derive)
+            .unwrap()
+            .to_string();
+
+        assert!(expanded.contains("fn content_security_policy"));
+        assert!(expanded.contains("\"csp.rs\" , \"script-src 'sha256-"));
+
+        let input = syn::parse_quote! {
+            #[embed(folder = "src", csp_policy = "script-src 'self'")]
+            struct Assets;
+        };
+        let message = expand_derive_embed(input, &Target::Derive)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("`csp_policy` requires `csp` attribute"));
+    }
+
+    #[test]
+    fn virtual_files() {
+        let input = syn::parse_quote! {
+            #[embed(virtual(path = "/version.json", content = env!("CARGO_PKG_VERSION")))]
+            #[embed(virtual(path = "config.js", content = "{}", content_type = "text/javascript"))]
+            #[embed(etag = "blake3")]
+            struct Assets;
+        };
+        let expanded = expand_derive_embed(input, &Target::Derive)
+            .unwrap()
+            .to_string();
+
+        assert!(expanded.contains("\"version.json\""));
+        assert!(expanded.contains("env ! (\"CARGO_PKG_VERSION\")"));
+        assert!(expanded.contains("require_blake3"));
+        assert!(expanded.contains("fn etag"));
+        assert!(expanded.contains("hasher :: Strength , headers"));
+
+        // no unused imports without virtual files
+        let expanded = expand_derive_embed(
+            syn::parse_quote! { #[embed(file = "Cargo.toml")] struct Assets; },
+            &Target::Derive,
+        )
+        .unwrap()
+        .to_string();
+        assert!(expanded.contains("fn __virtual_files"));
+        assert!(!expanded.contains("hasher :: Strength , headers"));
+
+        let error = |input| match expand_derive_embed(input, &Target::Derive) {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.to_string(),
+        };
+        let message = error(syn::parse_quote! {
+            #[embed(virtual(path = "version.json"))]
+            struct Assets;
+        });
+        assert!(message.contains("requires `path` and `content`"));
+        let message = error(syn::parse_quote! {
+            #[embed(virtual(path = "a.json", content = "{}", content_type = "json"))]
+            struct Assets;
+        });
+        assert!(message.contains("invalid `content_type`"));
+        let message = error(syn::parse_quote! {
+            #[embed(virtual(path = "a.json", content = "{}"), virtual(path = "/a.json", content = "[]"))]
+            struct Assets;
+        });
+        assert!(message.contains("duplicate path `a.json`"));
+    }
+
+    #[test]
+    fn duplicate_single_file_paths() {
+        let error = |input| match expand_derive_embed(input, &Target::Derive) {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err.to_string(),
+        };
+        let message = error(syn::parse_quote! {
+            #[embed(file = "Cargo.toml", file = "src/lib.rs", path = "/Cargo.toml")]
+            struct Assets;
+        });
+        assert!(message.contains("duplicate path `Cargo.toml`"));
+        let message = error(syn::parse_quote! {
+            #[embed(file = "Cargo.toml", virtual(path = "Cargo.toml", content = ""))]
+            struct Assets;
+        });
+        assert!(message.contains("also served by a virtual file"));
+    }
+
+    #[test]
+    fn substitute_placeholders() {
+        let root = TempDir::new("substitute-placeholders");
+        std::fs::write(root.join("index.html"), "<p>{{NAME}} %VERSION%</p>").unwrap();
+        std::fs::write(root.join("style.css"), "p { width: 100%; }").unwrap();
+        let folder = root.as_str();
+
+        let input = syn::parse_quote! {
+            #[embed(folder = #folder, substitute = "*.html")]
+            #[embed(vars(NAME = "app", VERSION = env!("CARGO_PKG_VERSION")))]
+            struct Assets;
+        };
+        let expanded = expand_derive_embed(input, &Target::Derive)
+            .unwrap()
+            .to_string();
+        let content = format!("<p>app {}</p>", env!("CARGO_PKG_VERSION"));
+        let content = proc_macro2::Literal::byte_string(content.as_bytes()).to_string();
+        assert!(expanded.contains(&content));
+        assert!(expanded.contains("env ! (\"CARGO_PKG_VERSION\")"));
+
+        let input = syn::parse_quote! {
+            #[embed(folder = #folder, substitute = "*.html", vars(NAME = "app"))]
+            struct Assets;
+        };
+        let err = expand_derive_embed(input, &Target::Derive).err().unwrap();
+        assert!(
+            err.to_string()
+                .contains("index.html: unknown placeholder `VERSION` at line 1")
+        );
+
+        let input = syn::parse_quote! {
+            #[embed(folder = #folder, vars(NAME = "app"))]
+            struct Assets;
+        };
+        let err = expand_derive_embed(input, &Target::Derive).err().unwrap();
+        assert!(err.to_string().contains("`vars` requires `substitute`"));
+    }
+
+    #[test]
+    fn embed_macro() {
+        let expanded = expand_embed(quote::quote! { "src", packed = true })
+            .unwrap()
+            .to_string();
+        assert!(expanded.contains("impl tower_embed :: Embed for Assets"));
+        assert!(expanded.contains("static ARCHIVE"));
+
+        let expanded = expand_embed(quote::quote! { file = "Cargo.toml" })
+            .unwrap()
+            .to_string();
+        assert!(expanded.contains("\"Cargo.toml\""));
+
+        assert!(expand_embed(quote::quote! { "src" extension = "rs" }).is_err());
+    }
+
+    /// Returns the path of the file included by the expansion with the extension.
+    fn included_file(expanded: &str, extension: &str) -> std::path::PathBuf {
+        let path = expanded
+            .split('"')
+            .find(|token| token.ends_with(extension))
+            .unwrap();
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join(path.trim_start_matches('/'))
+            .into()
+    }
+
+    #[test]
+    fn archives_of_implementations() {
+        let root = TempDir::new("archives-of-implementations");
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::create_dir_all(root.join("b")).unwrap();
+        std::fs::write(root.join("a/index.html"), "a").unwrap();
+        std::fs::write(root.join("b/index.html"), "b").unwrap();
+        let folder_a = root.join("a").into_string();
+        let folder_b = root.join("b").into_string();
+
+        let expand = |folder: &str| {
+            expand_embed(quote::quote! { #folder, packed = true })
+                .unwrap()
+                .to_string()
+        };
+        let archive_a = included_file(&expand(&folder_a), ".pack");
+        let archive_b = included_file(&expand(&folder_b), ".pack");
+        assert_ne!(archive_a, archive_b);
+        assert!(archive_a.is_file());
+        assert!(archive_b.is_file());
+
+        // the archive of the previous expansion is replaced
+        std::fs::write(root.join("a/index.html"), "a2").unwrap();
+        let archive_a2 = included_file(&expand(&folder_a), ".pack");
+        assert_ne!(archive_a, archive_a2);
+        assert!(!archive_a.exists());
+        assert!(archive_a2.is_file());
+        assert!(archive_b.is_file());
+
+        std::fs::remove_file(archive_a2).unwrap();
+        std::fs::remove_file(archive_b).unwrap();
+    }
+
+    #[test]
+    fn compressed_contents_of_implementations() {
+        let root = TempDir::new("compressed-contents-of-implementations");
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::create_dir_all(root.join("b")).unwrap();
+        std::fs::write(root.join("a/index.html"), "a".repeat(1024)).unwrap();
+        std::fs::write(root.join("b/index.html"), "b".repeat(1024)).unwrap();
+        let folder_a = root.join("a").into_string();
+        let folder_b = root.join("b").into_string();
+
+        let expand = |folder: &str| {
+            expand_embed(quote::quote! { #folder, storage = "zstd" })
+                .unwrap()
+                .to_string()
+        };
+        let content_a = included_file(&expand(&folder_a), ".zst");
+        let content_b = included_file(&expand(&folder_b), ".zst");
+        assert_ne!(content_a.parent(), content_b.parent());
+        assert!(content_a.is_file());
+        assert!(content_b.is_file());
+
+        // the contents of the previous expansion are removed
+        std::fs::write(root.join("a/index.html"), "c".repeat(1024)).unwrap();
+        let content_a2 = included_file(&expand(&folder_a), ".zst");
+        assert!(!content_a.exists());
+        assert!(content_a2.is_file());
+        assert!(content_b.is_file());
+
+        std::fs::remove_dir_all(content_a2.parent().unwrap()).unwrap();
+        std::fs::remove_dir_all(content_b.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn nearest_not_found_pages() {
+        let root = TempDir::new("nearest-not-found-pages");
+        for path in [
+            "404.html",
+            "docs/404.html",
+            "docs/guide/intro.html",
+            "docs/api/v1/index.html",
+            "blog/post.html",
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let files =
+            get_files(&root, root.as_str(), &[], &ETagConfig::default()).collect::<Vec<_>>();
+        let pages = nearest_pages(&files, "404.html");
+        assert_eq!(
+            pages.iter().collect::<Vec<_>>(),
+            [
+                (&"".to_string(), &"404.html".to_string()),
+                (&"blog".to_string(), &"404.html".to_string()),
+                (&"docs".to_string(), &"docs/404.html".to_string()),
+                (&"docs/api".to_string(), &"docs/404.html".to_string()),
+                (&"docs/api/v1".to_string(), &"docs/404.html".to_string()),
+                (&"docs/guide".to_string(), &"docs/404.html".to_string()),
+            ]
+        );
+
+        // without the root page, only the directories with a page in their ancestors have one
+        std::fs::remove_file(root.join("404.html")).unwrap();
+        let files =
+            get_files(&root, root.as_str(), &[], &ETagConfig::default()).collect::<Vec<_>>();
+        let pages = nearest_pages(&files, "404.html");
+        assert_eq!(
+            pages.keys().collect::<Vec<_>>(),
+            ["docs", "docs/api", "docs/api/v1", "docs/guide"]
+        );
+        assert!(nearest_pages(&files, "missing.html").is_empty());
+    }
+
+    #[test]
+    fn fingerprinted_paths() {
+        let path = |path: &str| fingerprinted_path(Path::new(path), "0123abcd");
+        assert_eq!(path("style.css"), "style.0123abcd.css");
+        assert_eq!(path("js/app.min.js"), "js/app.min.0123abcd.js");
+        assert_eq!(path("LICENSE"), "LICENSE.0123abcd");
+        assert_eq!(path("fonts/.hidden"), "fonts/.hidden.0123abcd");
+        assert_eq!(path(".env.local"), ".env.0123abcd.local");
+    }
+
+    #[test]
+    fn fingerprinted_lookup() {
+        let root = TempDir::new("fingerprinted-lookup");
+        std::fs::create_dir_all(root.join("css")).unwrap();
+        std::fs::write(root.join("css/style.css"), "p {}").unwrap();
+        std::fs::write(root.join("LICENSE"), "MIT").unwrap();
+        std::fs::write(root.join("index.html"), "<p></p>").unwrap();
+        let folder = root.as_str();
+        let hash =
+            |content: &[u8]| ETagConfig::default().compute(content).value()[..16].to_string();
+        let style = format!("css/style.{}.css", hash(b"p {}"));
+        let license = format!("LICENSE.{}", hash(b"MIT"));
+
+        let expanded = expand_embed(quote::quote! {
+            #folder, fingerprint = "{css/*.css,LICENSE}", packed = true
+        })
+        .unwrap()
+        .to_string();
+        for (path, url) in [("css/style.css", &style), ("LICENSE", &license)] {
+            assert!(expanded.contains(&format!("\"{path}\" , \"{url}\"")));
+            assert!(expanded.contains(&format!("\"/{path}\" , \"/{url}\"")));
+        }
+
+        let archive = std::fs::read(included_file(&expanded, ".pack")).unwrap();
+        let files = pack::read(archive.leak());
+        let content = |path: &str| match files.get(path) {
+            Some(pack::Entry::File(content, metadata)) => (*content, metadata.immutable),
+            _ => panic!("missing file `{path}`"),
+        };
+        assert_eq!(content("css/style.css"), (&b"p {}"[..], false));
+        assert_eq!(content(&style), (&b"p {}"[..], true));
+        assert_eq!(content("LICENSE"), (&b"MIT"[..], false));
+        assert_eq!(content(&license), (&b"MIT"[..], true));
+        assert_eq!(content("index.html"), (&b"<p></p>"[..], false));
+        // and the redirects of the root directory to its index
+        assert_eq!(files.len(), 7);
+
+        std::fs::remove_file(included_file(&expanded, ".pack")).unwrap();
+
+        // without the archive both paths are entries of the table of the files
+        let expanded = expand_embed(quote::quote! { #folder, fingerprint = "css/*.css" })
+            .unwrap()
+            .to_string();
+        assert!(expanded.contains(&format!(
+            "(\"{style}\" , Entry :: File (content , metadata . clone () . immutable (true)))"
+        )));
+        assert!(expanded.contains("(\"css/style.css\" , Entry :: File (content , metadata))"));
+    }
+
+    #[test]
+    fn relative_archive_path() {
+        let base = Path::new("/work/app");
+        assert_eq!(
+            relative_path(base, Path::new("/work/target/tower-embed/app.pack")).unwrap(),
+            "../target/tower-embed/app.pack"
+        );
+        assert_eq!(relative_path(base, Path::new("/tmp/app.pack")), None);
+    }
+
+    #[test]
+    fn deduplicate_contents() {
+        let root = TempDir::new("deduplicate-contents");
+        std::fs::create_dir_all(root.join("theme")).unwrap();
+        std::fs::write(root.join("font.woff2"), b"font").unwrap();
+        std::fs::write(root.join("logo.png"), b"logo").unwrap();
+        std::fs::write(root.join("theme/font.woff2"), b"font").unwrap();
+
+        let files =
+            get_files(&root, root.as_str(), &[], &ETagConfig::default()).collect::<Vec<_>>();
+        let contents = Contents::deduplicate(&files);
+        assert_eq!(contents.files.len(), 2);
+        assert_eq!(contents.duplicates, 1);
+        assert_eq!(contents.saved, 4);
+        assert_eq!(
+            contents.indexes["font.woff2"],
+            contents.indexes["theme/font.woff2"]
+        );
+        assert_ne!(contents.indexes["font.woff2"], contents.indexes["logo.png"]);
+    }
+}
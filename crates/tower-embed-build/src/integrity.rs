@@ -0,0 +1,61 @@
+//! Subresource Integrity digests, see <https://www.w3.org/TR/SRI/>.
+
+/// The hash algorithms supported by Subresource Integrity.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Algorithm {
+    Sha256,
+    #[default]
+    Sha384,
+    Sha512,
+}
+
+impl Algorithm {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "sha256" => Ok(Self::Sha256),
+            "sha384" => Ok(Self::Sha384),
+            "sha512" => Ok(Self::Sha512),
+            _ => Err(format!(
+                "unknown integrity algorithm `{value}`, expected `sha256`, `sha384` or `sha512`"
+            )),
+        }
+    }
+
+    /// Returns the integrity metadata of the content, like `sha384-<base64 digest>`.
+    #[cfg(feature = "integrity")]
+    pub fn integrity(self, content: &[u8]) -> String {
+        use base64::Engine;
+        use sha2::Digest;
+
+        let (prefix, digest) = match self {
+            Self::Sha256 => ("sha256", sha2::Sha256::digest(content).to_vec()),
+            Self::Sha384 => ("sha384", sha2::Sha384::digest(content).to_vec()),
+            Self::Sha512 => ("sha512", sha2::Sha512::digest(content).to_vec()),
+        };
+        let digest = base64::engine::general_purpose::STANDARD.encode(digest);
+        format!("{prefix}-{digest}")
+    }
+
+    #[cfg(not(feature = "integrity"))]
+    pub fn integrity(self, _content: &[u8]) -> String {
+        unreachable!("the feature is checked when the attribute is parsed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integrity_metadata() {
+        let content = b"alert('Hello, world.');";
+        assert_eq!(
+            Algorithm::Sha384.integrity(content),
+            "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO"
+        );
+        assert_eq!(
+            Algorithm::Sha256.integrity(b""),
+            "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Modes resolving the `Last-Modified` time of embedded files.
+
+use std::collections::BTreeMap;
+
+use camino::Utf8Path as Path;
+
+/// The source of the modification time of embedded files.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Mode {
+    /// The modification time of the file in the filesystem.
+    #[default]
+    Mtime,
+    /// The time in the `SOURCE_DATE_EPOCH` environment variable.
+    SourceDateEpoch,
+    /// The time of the last commit changing the file.
+    Git,
+    /// No modification time.
+    None,
+    /// A fixed time, as seconds since the Unix epoch.
+    Fixed(u64),
+}
+
+impl Mode {
+    pub fn parse(value: &syn::Lit) -> syn::Result<Self> {
+        match value {
+            syn::Lit::Str(value) => match value.value().as_str() {
+                "mtime" => Ok(Self::Mtime),
+                "source_date_epoch" => Ok(Self::SourceDateEpoch),
+                "git" => Ok(Self::Git),
+                "none" => Ok(Self::None),
+                _ => Err(syn::Error::new_spanned(
+                    value,
+                    "unknown `last_modified` source, expected `mtime`, `source_date_epoch`, \
+                     `git`, `none` or a Unix timestamp",
+                )),
+            },
+            syn::Lit::Int(value) => value.base10_parse().map(Self::Fixed),
+            _ => Err(syn::Error::new_spanned(
+                value,
+                "expected a string or a Unix timestamp",
+            )),
+        }
+    }
+}
+
+/// Resolves the modification time of the files in a folder.
+///
+/// The files whose commit time cannot be resolved, like files not committed in git, fall back to
+/// the modification time in the filesystem.
+pub struct Resolver {
+    mode: Mode,
+    commit_times: BTreeMap<String, u64>,
+}
+
+impl Resolver {
+    /// Create a resolver of the files in the folder, if any.
+    ///
+    /// Returns an error if the source is `SOURCE_DATE_EPOCH` and the variable is not set or
+    /// invalid.
+    pub fn new(mode: Mode, root: Option<&Path>) -> Result<Self, String> {
+        let (mode, commit_times) = match mode {
+            Mode::SourceDateEpoch => match std::env::var("SOURCE_DATE_EPOCH") {
+                Ok(epoch) => {
+                    let epoch = epoch
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("invalid SOURCE_DATE_EPOCH `{epoch}`"))?;
+                    (Mode::Fixed(epoch), BTreeMap::new())
+                }
+                Err(_) => {
+                    return Err(
+                        "`SOURCE_DATE_EPOCH` environment variable is not set, it is \
+                                required by `last_modified = \"source_date_epoch\"`"
+                            .to_string(),
+                    );
+                }
+            },
+            Mode::Git => (
+                Mode::Git,
+                root.and_then(git_commit_times).unwrap_or_default(),
+            ),
+            mode => (mode, BTreeMap::new()),
+        };
+
+        Ok(Self { mode, commit_times })
+    }
+
+    /// Returns the modification time of the file, as seconds since the Unix epoch.
+    pub fn resolve(&self, relative_path: &Path, absolute_path: &Path) -> Option<u64> {
+        match self.mode {
+            Mode::None => None,
+            Mode::Fixed(secs) => Some(secs),
+            Mode::Git => match self.commit_times.get(relative_path.as_str()) {
+                Some(secs) => Some(*secs),
+                None => mtime(absolute_path),
+            },
+            Mode::Mtime | Mode::SourceDateEpoch => mtime(absolute_path),
+        }
+    }
+
+    /// Returns the modification time of a virtual file, only a fixed time applies.
+    pub fn resolve_virtual(&self) -> Option<u64> {
+        match self.mode {
+            Mode::Fixed(secs) => Some(secs),
+            _ => None,
+        }
+    }
+
+    /// Returns the modification time of a file outside of the folder.
+    pub fn resolve_file(&self, path: &Path) -> Option<u64> {
+        match self.mode {
+            Mode::Git => git_commit_time(path).or_else(|| mtime(path)),
+            _ => self.resolve(path, path),
+        }
+    }
+}
+
+fn mtime(path: &Path) -> Option<u64> {
+    let tower_embed_core::headers::LastModified(time) =
+        tower_embed_core::last_modified(path.as_std_path()).ok()?;
+    time.duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .ok()
+}
+
+/// Returns the time of the last commit changing each file in the folder, with a single pass over
+/// the history.
+fn git_commit_times(root: &Path) -> Option<BTreeMap<String, u64>> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args([
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--format=%x00%ct",
+            "--name-only",
+            "--relative",
+            "--",
+            ".",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(parse_git_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Returns the time of the last commit changing the file.
+fn git_commit_time(path: &Path) -> Option<u64> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(path.parent()?)
+        .args(["log", "-1", "--format=%ct", "--"])
+        .arg(path.file_name()?)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Parses the output of `git log --format=%x00%ct --name-only`, the commits are sorted from the
+/// newest, so the first time of each file is the last one.
+fn parse_git_log(output: &str) -> BTreeMap<String, u64> {
+    let mut commit_times = BTreeMap::new();
+    for commit in output.split('\0').filter(|commit| !commit.is_empty()) {
+        let mut lines = commit.lines();
+        let Some(Ok(time)) = lines.next().map(str::parse::<u64>) else {
+            continue;
+        };
+        for path in lines.filter(|line| !line.is_empty()) {
+            commit_times.entry(path.to_string()).or_insert(time);
+        }
+    }
+    commit_times
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn git_log() {
+        let output = "\
+\x001700000300
+
+index.html
+\x001700000200
+
+css/site.css
+index.html
+\x001700000100
+
+404.html
+";
+        let commit_times = parse_git_log(output);
+        assert_eq!(commit_times["index.html"], 1700000300);
+        assert_eq!(commit_times["css/site.css"], 1700000200);
+        assert_eq!(commit_times["404.html"], 1700000100);
+    }
+
+    #[test]
+    fn parse_mode() {
+        let mode = |value: syn::Lit| Mode::parse(&value);
+        assert_eq!(mode(syn::parse_quote!("git")).unwrap(), Mode::Git);
+        assert_eq!(
+            mode(syn::parse_quote!(1700000000)).unwrap(),
+            Mode::Fixed(1700000000)
+        );
+        assert!(mode(syn::parse_quote!("ctime")).is_err());
+        assert!(mode(syn::parse_quote!(-1)).is_err());
+    }
+}
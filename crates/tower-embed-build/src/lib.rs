@@ -0,0 +1,521 @@
+//! Generate the `Embed` implementation of tower-embed from build scripts.
+//!
+//! The derive macro walks the embedded folder on every expansion, and it cannot declare the files
+//! to rebuild on change. From a build script, the same implementation is generated once into
+//! `OUT_DIR`, and cargo reruns the build script only when a file or directory of the folder
+//! changes.
+//!
+//! # Usage
+//!
+//! ```no_run
+//! // in `main` of build.rs
+//! tower_embed_build::Builder::new("assets")
+//!     .storage(tower_embed_build::Storage::Zstd)
+//!     .write("assets.rs")
+//!     .unwrap();
+//! ```
+//!
+//! The generated file implements [`Embed`] for the unit struct named `Assets`, see
+//! [`Builder::name`], it is included with `include!`:
+//!
+//! ```ignore
+//! struct Assets;
+//!
+//! include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+//! ```
+//!
+//! # Features
+//!
+//! The attributes hashing, compressing or parsing the files require the feature of the same name
+//! of `tower-embed` in the derive macro, and of this crate in build scripts: `sha256` and
+//! `blake3` for the entity tags, `zstd` and `brotli` for the storage, `integrity`, `csp` and
+//! `manifest`. The storage features of `tower-embed` are still needed to decompress the contents.
+//!
+//! [`Embed`]: https://docs.rs/tower-embed/latest/tower_embed/trait.Embed.html
+
+use std::fmt;
+
+use camino::Utf8PathBuf as PathBuf;
+
+use self::derive::Target;
+
+mod csp;
+mod derive;
+mod integrity;
+mod last_modified;
+mod manifest;
+mod rules;
+mod storage;
+
+/// Expands the derive macro of `Embed`.
+#[doc(hidden)]
+pub fn __derive_embed(input: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    syn::parse2(input)
+        .and_then(|input| derive::expand_derive_embed(input, &Target::Derive))
+        .unwrap_or_else(|err| err.to_compile_error())
+}
+
+/// Expands the function-like `embed!` macro.
+#[doc(hidden)]
+pub fn __embed(input: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    derive::expand_embed(input).unwrap_or_else(|err| err.to_compile_error())
+}
+
+/// Builder of the `Embed` implementation of a folder.
+///
+/// The options are the same of the `#[embed(...)]` attributes of the derive macro, and they are
+/// validated when the implementation is written.
+pub struct Builder {
+    folder: String,
+    files: Vec<String>,
+    name: String,
+    attrs: Vec<proc_macro2::TokenStream>,
+    /// The first option that cannot be converted into an attribute
+    invalid: Option<String>,
+}
+
+impl Builder {
+    /// Create a new [`Builder`] embedding the folder, relative to `CARGO_MANIFEST_DIR`.
+    pub fn new(folder: impl Into<String>) -> Self {
+        Self {
+            folder: folder.into(),
+            files: Vec::new(),
+            name: "Assets".to_string(),
+            attrs: Vec::new(),
+            invalid: None,
+        }
+    }
+
+    /// Set the name of the unit struct implementing `Embed`, the default is `Assets`.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Embed a single file served at the path, see `#[embed(file = "...", path = "...")]`.
+    pub fn file(mut self, file: &str, path: &str) -> Self {
+        self.files.push(file.to_string());
+        self.attr(quote::quote! { file = #file, path = #path })
+    }
+
+    /// Set the path of the `tower-embed` crate, see `#[embed(crate = ...)]`.
+    pub fn crate_path(self, path: &str) -> Self {
+        match syn::parse_str::<syn::Path>(path) {
+            Ok(path) => self.attr(quote::quote! { crate = #path }),
+            Err(_) => self.invalid(format!("invalid crate path `{path}`")),
+        }
+    }
+
+    /// Add a name of the index file of directories, see `#[embed(index = "...")]`.
+    pub fn index(self, name: &str) -> Self {
+        self.attr(quote::quote! { index = #name })
+    }
+
+    /// Add an extension that can be omitted from URLs, see `#[embed(extension = "...")]`.
+    pub fn extension(self, extension: &str) -> Self {
+        self.attr(quote::quote! { extension = #extension })
+    }
+
+    /// Set the name of the not found pages, see `#[embed(not_found = "...")]`.
+    pub fn not_found(self, name: &str) -> Self {
+        self.attr(quote::quote! { not_found = #name })
+    }
+
+    /// Load custom headers from a file, see `#[embed(headers_file = "...")]`.
+    pub fn headers_file(self, file: &str) -> Self {
+        self.attr(quote::quote! { headers_file = #file })
+    }
+
+    /// Load redirect rules from a file, see `#[embed(redirects_file = "...")]`.
+    pub fn redirects_file(self, file: &str) -> Self {
+        self.attr(quote::quote! { redirects_file = #file })
+    }
+
+    /// Serve the files matching the glob pattern at a fingerprinted path, see
+    /// `#[embed(fingerprint = "...")]`.
+    pub fn fingerprint(self, pattern: &str) -> Self {
+        self.attr(quote::quote! { fingerprint = #pattern })
+    }
+
+    /// Parse the build manifest of a bundler, see `#[embed(manifest = "...")]`.
+    pub fn manifest(self, format: ManifestFormat) -> Self {
+        let format = format.as_str();
+        self.attr(quote::quote! { manifest = #format })
+    }
+
+    /// Set the path of the build manifest, see `#[embed(manifest_file = "...")]`.
+    pub fn manifest_file(self, file: &str) -> Self {
+        self.attr(quote::quote! { manifest_file = #file })
+    }
+
+    /// Compute the Subresource Integrity metadata of the files matching the glob pattern, see
+    /// `#[embed(integrity = "...")]`.
+    pub fn integrity(self, pattern: &str) -> Self {
+        self.attr(quote::quote! { integrity = #pattern })
+    }
+
+    /// Set the hash algorithm of Subresource Integrity metadata, see
+    /// `#[embed(integrity_algorithm = "...")]`.
+    pub fn integrity_algorithm(self, algorithm: IntegrityAlgorithm) -> Self {
+        let algorithm = algorithm.as_str();
+        self.attr(quote::quote! { integrity_algorithm = #algorithm })
+    }
+
+    /// Compute the `Content-Security-Policy` of the HTML files matching the glob pattern, see
+    /// `#[embed(csp = "...")]`.
+    pub fn csp(self, pattern: &str) -> Self {
+        self.attr(quote::quote! { csp = #pattern })
+    }
+
+    /// Set the template of the `Content-Security-Policy`, see `#[embed(csp_policy = "...")]`.
+    pub fn csp_policy(self, policy: &str) -> Self {
+        self.attr(quote::quote! { csp_policy = #policy })
+    }
+
+    /// Set the hash algorithm of entity tags, see `#[embed(etag = "...")]`.
+    pub fn etag(self, algorithm: ETagAlgorithm) -> Self {
+        let algorithm = algorithm.as_str();
+        self.attr(quote::quote! { etag = #algorithm })
+    }
+
+    /// Set the strength of entity tags, see `#[embed(etag_strength = "...")]`.
+    pub fn etag_strength(self, strength: ETagStrength) -> Self {
+        let strength = strength.as_str();
+        self.attr(quote::quote! { etag_strength = #strength })
+    }
+
+    /// Set the source of the `Last-Modified` time, see `#[embed(last_modified = "...")]`.
+    pub fn last_modified(self, source: LastModified) -> Self {
+        let source = match source {
+            LastModified::Mtime => quote::quote! { "mtime" },
+            LastModified::SourceDateEpoch => quote::quote! { "source_date_epoch" },
+            LastModified::Git => quote::quote! { "git" },
+            LastModified::None => quote::quote! { "none" },
+            LastModified::Fixed(secs) => {
+                let secs = proc_macro2::Literal::u64_unsuffixed(secs);
+                quote::quote! { #secs }
+            }
+        };
+        self.attr(quote::quote! { last_modified = #source })
+    }
+
+    /// Store the contents compressed, see `#[embed(storage = "...")]`.
+    pub fn storage(self, storage: Storage) -> Self {
+        let storage = storage.as_str();
+        self.attr(quote::quote! { storage = #storage })
+    }
+
+    /// Pack the files in a single archive, see `#[embed(packed = ...)]`.
+    pub fn packed(self, packed: bool) -> Self {
+        self.attr(quote::quote! { packed = #packed })
+    }
+
+    /// Substitute the placeholders of the text files matching the glob pattern, see
+    /// `#[embed(substitute = "...")]`.
+    pub fn substitute(self, pattern: &str) -> Self {
+        self.attr(quote::quote! { substitute = #pattern })
+    }
+
+    /// Set the value of a variable of the placeholders, see `#[embed(vars(NAME = "..."))]`.
+    pub fn var(self, name: &str, value: &str) -> Self {
+        match syn::parse_str::<syn::Ident>(name) {
+            Ok(name) => self.attr(quote::quote! { vars(#name = #value) }),
+            Err(_) => self.invalid(format!("invalid variable name `{name}`")),
+        }
+    }
+
+    fn attr(mut self, attr: proc_macro2::TokenStream) -> Self {
+        self.attrs.push(attr);
+        self
+    }
+
+    /// Records an invalid option, reported when the implementation is written.
+    fn invalid(mut self, message: String) -> Self {
+        self.invalid.get_or_insert(message);
+        self
+    }
+
+    /// Write the implementation in a file of `OUT_DIR`, and declare the files and directories of
+    /// the folder, and the single files, to rerun the build script on change.
+    pub fn write(self, file: &str) -> Result<(), Error> {
+        let out_dir = std::env::var("OUT_DIR")
+            .map_err(|_| Error::new("missing OUT_DIR, `write` must be called by a build script"))?;
+        let out_dir = PathBuf::from(out_dir);
+
+        let code = self.generate(&out_dir)?;
+        let path = out_dir.join(file);
+        std::fs::write(&path, code)
+            .map_err(|err| Error::new(format!("failed to write `{}`: {}", path, err)))?;
+
+        let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
+            .map_err(|_| Error::new("missing CARGO_MANIFEST_DIR environment variable"))?;
+        let root = std::path::Path::new(&manifest_dir).join(&self.folder);
+        for entry in walkdir::WalkDir::new(&root).follow_links(true) {
+            let entry = entry.map_err(|err| Error::new(err.to_string()))?;
+            println!("cargo:rerun-if-changed={}", entry.path().display());
+        }
+        for file in &self.files {
+            let path = std::path::Path::new(&manifest_dir).join(file);
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+        println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+        Ok(())
+    }
+
+    /// Returns the code of the implementation, the packed archive is written in `out_dir`.
+    fn generate(&self, out_dir: &camino::Utf8Path) -> Result<String, Error> {
+        if let Some(message) = &self.invalid {
+            return Err(Error::new(message.clone()));
+        }
+        let folder = &self.folder;
+        let name = syn::parse_str::<syn::Ident>(&self.name)
+            .map_err(|_| Error::new(format!("invalid struct name `{}`", self.name)))?;
+        let attrs = &self.attrs;
+        let input = syn::parse_quote! {
+            #[embed(folder = #folder #(, #attrs)*)]
+            struct #name;
+        };
+
+        let target = Target::BuildScript(out_dir.to_path_buf());
+        let code = derive::expand_derive_embed(input, &target)
+            .map_err(|err| Error::new(err.to_string()))?;
+        Ok(code.to_string())
+    }
+}
+
+/// The build manifest formats, see [`Builder::manifest`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// The manifest generated by Vite with `build.manifest`.
+    Vite,
+    /// The manifest generated by `webpack-manifest-plugin`.
+    Webpack,
+    /// The `index.html` page generated by Trunk, linking the hashed files.
+    Trunk,
+}
+
+impl ManifestFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Vite => "vite",
+            Self::Webpack => "webpack",
+            Self::Trunk => "trunk",
+        }
+    }
+}
+
+/// The hash algorithms of Subresource Integrity metadata, see [`Builder::integrity_algorithm`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntegrityAlgorithm {
+    Sha256,
+    #[default]
+    Sha384,
+    Sha512,
+}
+
+impl IntegrityAlgorithm {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        }
+    }
+}
+
+/// The hash algorithms of entity tags, see [`Builder::etag`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ETagAlgorithm {
+    #[default]
+    RapidHash,
+    /// Requires the `sha256` feature.
+    Sha256,
+    /// Requires the `blake3` feature.
+    Blake3,
+}
+
+impl ETagAlgorithm {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::RapidHash => "rapidhash",
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+        }
+    }
+}
+
+/// The strength of entity tags, see [`Builder::etag_strength`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ETagStrength {
+    #[default]
+    Strong,
+    Weak,
+}
+
+impl ETagStrength {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Strong => "strong",
+            Self::Weak => "weak",
+        }
+    }
+}
+
+/// The sources of the `Last-Modified` time, see [`Builder::last_modified`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LastModified {
+    /// The modification time of the file in the filesystem.
+    #[default]
+    Mtime,
+    /// The time in the `SOURCE_DATE_EPOCH` environment variable.
+    SourceDateEpoch,
+    /// The time of the last commit changing the file.
+    Git,
+    /// No modification time.
+    None,
+    /// A fixed time, as seconds since the Unix epoch.
+    Fixed(u64),
+}
+
+/// How the contents of the files are stored in the binary, see [`Builder::storage`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Storage {
+    /// The contents are stored as they are.
+    #[default]
+    Raw,
+    /// The contents are stored compressed with Zstandard, requires the `zstd` feature.
+    Zstd,
+    /// The contents are stored compressed with Brotli, requires the `brotli` feature.
+    Brotli,
+}
+
+impl Storage {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::Zstd => "zstd",
+            Self::Brotli => "brotli",
+        }
+    }
+}
+
+/// An error generating the `Embed` implementation.
+#[derive(Debug)]
+pub struct Error {
+    message: String,
+}
+
+impl Error {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A temporary directory unique to the test, removed when dropped.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(test: &str) -> Self {
+            let name = format!("tower-embed-build-{}-{}", std::process::id(), test);
+            let dir = PathBuf::try_from(std::env::temp_dir()).unwrap().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = camino::Utf8Path;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn generate_implementation() {
+        let code = Builder::new("src")
+            .name("Sources")
+            .fingerprint("*.rs")
+            .last_modified(LastModified::Fixed(1700000000))
+            .generate(&TempDir::new("generate"))
+            .unwrap();
+
+        assert!(code.contains("impl tower_embed :: Embed for Sources"));
+        assert!(code.contains("1700000000"));
+    }
+
+    #[test]
+    fn packed_archive_in_out_dir() {
+        let out_dir = TempDir::new("packed");
+        let code = Builder::new("src").packed(true).generate(&out_dir).unwrap();
+
+        assert!(code.contains("OUT_DIR"));
+        assert!(!code.contains(out_dir.as_str()));
+        assert!(std::fs::read_dir(out_dir.as_std_path()).unwrap().count() > 0);
+    }
+
+    #[test]
+    fn substitute_variables() {
+        let out_dir = TempDir::new("substitute");
+        let code = Builder::new("src")
+            .substitute("lib.rs")
+            .var("VERSION", "1.0.0")
+            .generate(&out_dir)
+            .unwrap();
+        assert!(code.contains("const VARS"));
+
+        let err = Builder::new("src")
+            .substitute("lib.rs")
+            .var("APP VERSION", "1.0.0")
+            .generate(&out_dir)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "invalid variable name `APP VERSION`");
+    }
+
+    #[test]
+    fn typed_options() {
+        let code = Builder::new("src")
+            .etag(ETagAlgorithm::Sha256)
+            .etag_strength(ETagStrength::Weak)
+            .integrity("*.rs")
+            .integrity_algorithm(IntegrityAlgorithm::Sha512)
+            .storage(Storage::Zstd)
+            .generate(&TempDir::new("typed"))
+            .unwrap();
+
+        assert!(code.contains("require_sha256"));
+        assert!(code.contains("W/"));
+        assert!(code.contains("sha512-"));
+        assert!(code.contains("__storage :: zstd"));
+    }
+
+    #[test]
+    fn invalid_option() {
+        let err = Builder::new("src")
+            .crate_path("tower embed")
+            .generate(&TempDir::new("invalid-crate-path"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "invalid crate path `tower embed`");
+    }
+}
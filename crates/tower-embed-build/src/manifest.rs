@@ -0,0 +1,454 @@
+//! Parsers of build manifests generated by bundlers.
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "manifest")]
+use serde_json::Value;
+
+/// The supported build manifest formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// The manifest generated by Vite with `build.manifest`.
+    Vite,
+    /// The manifest generated by `webpack-manifest-plugin`.
+    Webpack,
+    /// The `index.html` page generated by Trunk, linking the hashed files.
+    Trunk,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "vite" => Ok(Self::Vite),
+            "webpack" => Ok(Self::Webpack),
+            "trunk" => Ok(Self::Trunk),
+            _ => Err(format!(
+                "unknown manifest format `{value}`, expected `vite`, `webpack` or `trunk`"
+            )),
+        }
+    }
+
+    /// The default paths of the manifest, relative to the embedded folder, in order of preference.
+    pub fn default_paths(self) -> &'static [&'static str] {
+        match self {
+            Self::Vite => &[".vite/manifest.json", "manifest.json"],
+            Self::Webpack => &["manifest.json"],
+            Self::Trunk => &["index.html"],
+        }
+    }
+
+    /// Returns true if the manifest is a page served with the other files.
+    pub fn is_served(self) -> bool {
+        self == Self::Trunk
+    }
+}
+
+/// An entry point of a build manifest.
+#[derive(Debug, Default, PartialEq)]
+pub struct Entry {
+    pub file: String,
+    pub css: Vec<String>,
+    pub preload: Vec<String>,
+}
+
+/// Parses a manifest, returning the entry points by source path.
+///
+/// The paths of the built files are root-relative URLs, like `/assets/main.js`.
+pub fn parse(format: Format, content: &str) -> Result<BTreeMap<String, Entry>, String> {
+    if format == Format::Trunk {
+        return parse_trunk(content);
+    }
+    parse_json(format, content)
+}
+
+/// Parses a manifest in JSON format.
+#[cfg(feature = "manifest")]
+fn parse_json(format: Format, content: &str) -> Result<BTreeMap<String, Entry>, String> {
+    let manifest = serde_json::from_str::<Value>(content).map_err(|err| err.to_string())?;
+    let Value::Object(manifest) = manifest else {
+        return Err("expected a JSON object".to_string());
+    };
+
+    match format {
+        Format::Vite => parse_vite(&manifest),
+        Format::Webpack => parse_webpack(&manifest),
+        Format::Trunk => unreachable!(),
+    }
+}
+
+#[cfg(not(feature = "manifest"))]
+fn parse_json(_format: Format, _content: &str) -> Result<BTreeMap<String, Entry>, String> {
+    unreachable!("the feature is checked when the attribute is parsed")
+}
+
+#[cfg(feature = "manifest")]
+type Object = serde_json::Map<String, Value>;
+
+#[cfg(feature = "manifest")]
+/// Parses a Vite manifest, chunks with a name starting with `_` are shared chunks and are not
+/// entry points.
+fn parse_vite(manifest: &Object) -> Result<BTreeMap<String, Entry>, String> {
+    let mut entries = BTreeMap::new();
+    for name in manifest.keys().filter(|name| !name.starts_with('_')) {
+        let mut entry = Entry {
+            file: vite_chunk_file(manifest, name)?,
+            ..Entry::default()
+        };
+
+        let mut visited = vec![name.as_str()];
+        collect_vite_chunk(manifest, name, &mut visited, &mut entry)?;
+        entries.insert(name.clone(), entry);
+    }
+    Ok(entries)
+}
+
+#[cfg(feature = "manifest")]
+/// Collects the stylesheets of the chunk, and the files and stylesheets of its static imports.
+fn collect_vite_chunk<'a>(
+    manifest: &'a Object,
+    name: &str,
+    visited: &mut Vec<&'a str>,
+    entry: &mut Entry,
+) -> Result<(), String> {
+    let chunk = &manifest[name];
+    for css in string_array(chunk, "css", name)? {
+        push_unique(&mut entry.css, css);
+    }
+
+    for import in string_array(chunk, "imports", name)? {
+        let Some((import, _)) = manifest.get_key_value(import) else {
+            return Err(format!("chunk `{name}` imports unknown chunk `{import}`"));
+        };
+        if visited.contains(&import.as_str()) {
+            continue;
+        }
+        visited.push(import);
+
+        push_unique(&mut entry.preload, &vite_chunk_file(manifest, import)?);
+        collect_vite_chunk(manifest, import, visited, entry)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "manifest")]
+fn vite_chunk_file(manifest: &Object, name: &str) -> Result<String, String> {
+    manifest[name]
+        .get("file")
+        .and_then(Value::as_str)
+        .map(url)
+        .ok_or_else(|| format!("chunk `{name}` has no `file`"))
+}
+
+#[cfg(feature = "manifest")]
+/// Parses a webpack manifest, mapping names to files, the stylesheet of a `.js` entry point is
+/// the file of the `.css` name with the same stem.
+fn parse_webpack(manifest: &Object) -> Result<BTreeMap<String, Entry>, String> {
+    let mut entries = BTreeMap::new();
+    for (name, file) in manifest {
+        let Some(file) = file.as_str() else {
+            return Err(format!("file of `{name}` is not a string"));
+        };
+
+        let css = name
+            .strip_suffix(".js")
+            .and_then(|stem| manifest.get(&format!("{stem}.css")))
+            .and_then(Value::as_str)
+            .map(url);
+
+        let entry = Entry {
+            file: url(file),
+            css: css.into_iter().collect(),
+            preload: Vec::new(),
+        };
+        entries.insert(name.clone(), entry);
+    }
+    Ok(entries)
+}
+
+/// Parses the `index.html` page generated by Trunk, where the files have a hash suffix like
+/// `app-1d2e3f4a5b6c7d8e.js`.
+///
+/// The entry points are the files linked by the page, by name without the hash. The JavaScript
+/// module initializing the application has the stylesheets and the other preloaded files of the
+/// page, like the WebAssembly module.
+fn parse_trunk(html: &str) -> Result<BTreeMap<String, Entry>, String> {
+    let mut modules = Vec::new();
+    let mut css = Vec::new();
+    let mut preload = Vec::new();
+    for (tag, attributes) in tags(html, &["link", "script"]) {
+        // the source tags of a page not built by Trunk
+        if attribute(attributes, "data-trunk").is_some() {
+            return Err("the page has `data-trunk` links, it is not built by Trunk".to_string());
+        }
+        let rel = attribute(attributes, "rel").map(str::to_ascii_lowercase);
+        let (href, list) = match (tag, rel.as_deref()) {
+            ("link", Some("stylesheet")) => (attribute(attributes, "href"), &mut css),
+            ("link", Some("modulepreload")) => (attribute(attributes, "href"), &mut modules),
+            ("link", Some("preload")) => (attribute(attributes, "href"), &mut preload),
+            ("script", _) => (attribute(attributes, "src"), &mut modules),
+            _ => continue,
+        };
+        if let Some(href) = href.filter(|href| !href.contains(':') && !href.starts_with("//")) {
+            push_unique(list, href);
+        }
+    }
+
+    let mut entries = BTreeMap::new();
+    for file in &modules {
+        let entry = Entry {
+            file: url(file),
+            css: css.clone(),
+            preload: preload.clone(),
+        };
+        entries.insert(unhashed_name(file), entry);
+    }
+    for file in css.iter().chain(&preload) {
+        entries.entry(unhashed_name(file)).or_insert_with(|| Entry {
+            file: url(file),
+            ..Entry::default()
+        });
+    }
+    if entries.is_empty() {
+        return Err("the page links no files built by Trunk".to_string());
+    }
+    Ok(entries)
+}
+
+/// Returns the tags with one of the names and their attributes, skipping comments.
+fn tags<'a>(html: &'a str, names: &[&'a str]) -> Vec<(&'a str, &'a str)> {
+    let mut tags = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let Some(name) = names
+            .iter()
+            .find(|name| rest[..name_end].eq_ignore_ascii_case(name))
+        else {
+            continue;
+        };
+        let end = tag_end(&rest[name_end..]).map_or(rest.len(), |end| name_end + end);
+        tags.push((*name, &rest[name_end..end]));
+        rest = &rest[end..];
+    }
+    tags
+}
+
+/// Returns the offset of the `>` closing the tag, skipping quoted attribute values.
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    tag.char_indices().find_map(|(offset, c)| {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, '>') => return Some(offset),
+            _ => {}
+        }
+        None
+    })
+}
+
+/// Returns the value of the attribute, an empty string for an attribute without value.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    loop {
+        let start = rest.find(|c: char| !c.is_ascii_whitespace() && c != '/')?;
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let matches = rest[..end].eq_ignore_ascii_case(name);
+        rest = rest[end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let value = value.trim_start();
+                let (value, tail) = match value.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        value[1..].split_once(quote).unwrap_or((&value[1..], ""))
+                    }
+                    _ => value.split_at(
+                        value
+                            .find(|c: char| c.is_ascii_whitespace())
+                            .unwrap_or(value.len()),
+                    ),
+                };
+                rest = tail;
+                value
+            }
+            None => "",
+        };
+        if matches {
+            return Some(value);
+        }
+    }
+}
+
+/// Returns the path of the file without the hash added by Trunk, like `app.js` for
+/// `/app-1d2e3f4a5b6c7d8e.js` and `app_bg.wasm` for `/app-1d2e3f4a5b6c7d8e_bg.wasm`.
+fn unhashed_name(file: &str) -> String {
+    let path = url(file)[1..].to_string();
+    let (dir, name) = path
+        .rsplit_once('/')
+        .map_or(("", path.as_str()), |(dir, name)| {
+            (&path[..dir.len() + 1], name)
+        });
+    let unhashed = name.rmatch_indices('-').find_map(|(start, _)| {
+        let rest = &name[start + 1..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(rest.len());
+        (len >= 8 && (rest[len..].starts_with('.') || rest[len..].starts_with("_bg.")))
+            .then(|| format!("{}{}", &name[..start], &rest[len..]))
+    });
+    format!("{dir}{}", unhashed.as_deref().unwrap_or(name))
+}
+
+/// Returns the root-relative URL of a file of the manifest.
+fn url(file: &str) -> String {
+    let file = file.trim_start_matches("./").trim_start_matches('/');
+    format!("/{file}")
+}
+
+#[cfg(feature = "manifest")]
+fn string_array<'a>(chunk: &'a Value, key: &str, name: &str) -> Result<Vec<&'a str>, String> {
+    let Some(value) = chunk.get(key) else {
+        return Ok(Vec::new());
+    };
+    value
+        .as_array()
+        .and_then(|values| values.iter().map(Value::as_str).collect())
+        .ok_or_else(|| format!("`{key}` of chunk `{name}` is not an array of strings"))
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    let value = url(value);
+    if !values.contains(&value) {
+        values.push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vite_manifest() {
+        let content = r#"{
+            "_shared-B7PI925R.js": {
+                "file": "assets/shared-B7PI925R.js",
+                "name": "shared",
+                "css": ["assets/shared-ChJ_j-JJ.css"]
+            },
+            "_vendor-Dx8a7Bc2.js": {
+                "file": "assets/vendor-Dx8a7Bc2.js",
+                "imports": ["_shared-B7PI925R.js"]
+            },
+            "src/main.ts": {
+                "file": "assets/main-4f2a1b.js",
+                "src": "src/main.ts",
+                "isEntry": true,
+                "imports": ["_vendor-Dx8a7Bc2.js", "_shared-B7PI925R.js"],
+                "dynamicImports": ["src/lazy.ts"],
+                "css": ["assets/main-5UjPuW-k.css"]
+            },
+            "src/lazy.ts": {
+                "file": "assets/lazy-Bc4lD2p8.js",
+                "src": "src/lazy.ts",
+                "isDynamicEntry": true
+            }
+        }"#;
+
+        let entries = parse(Format::Vite, content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries["src/main.ts"],
+            Entry {
+                file: "/assets/main-4f2a1b.js".to_string(),
+                css: vec![
+                    "/assets/main-5UjPuW-k.css".to_string(),
+                    "/assets/shared-ChJ_j-JJ.css".to_string(),
+                ],
+                preload: vec![
+                    "/assets/vendor-Dx8a7Bc2.js".to_string(),
+                    "/assets/shared-B7PI925R.js".to_string(),
+                ],
+            }
+        );
+        assert_eq!(entries["src/lazy.ts"].file, "/assets/lazy-Bc4lD2p8.js");
+    }
+
+    #[test]
+    fn invalid_vite_manifest() {
+        assert!(parse(Format::Vite, "[]").is_err());
+        assert!(parse(Format::Vite, r#"{"src/main.ts": {}}"#).is_err());
+        assert!(
+            parse(
+                Format::Vite,
+                r#"{"src/main.ts": {"file": "main.js", "imports": ["_missing.js"]}}"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn webpack_manifest() {
+        let content = r#"{
+            "main.js": "/static/main.1a2b3c.js",
+            "main.css": "/static/main.4d5e6f.css",
+            "logo.png": "/static/logo.7a8b9c.png"
+        }"#;
+
+        let entries = parse(Format::Webpack, content).unwrap();
+        assert_eq!(
+            entries["main.js"],
+            Entry {
+                file: "/static/main.1a2b3c.js".to_string(),
+                css: vec!["/static/main.4d5e6f.css".to_string()],
+                preload: Vec::new(),
+            }
+        );
+        assert_eq!(entries["logo.png"].file, "/static/logo.7a8b9c.png");
+    }
+
+    #[test]
+    fn trunk_page() {
+        let content = r#"<!DOCTYPE html><html><head>
+            <link rel="preload" href="/app-1d2e3f4a5b6c7d8e_bg.wasm" crossorigin=anonymous
+                integrity="sha384-abc" as="fetch" type="application/wasm">
+            <link rel="modulepreload" href="/app-1d2e3f4a5b6c7d8e.js" crossorigin=anonymous>
+            <link rel=stylesheet href="./css/styles-9f3a0b1c2d3e4f50.css">
+            <!-- <link rel="stylesheet" href="/old.css"> -->
+            <link rel="icon" href="/favicon.ico">
+            <script src="https://cdn.example.com/lib.js"></script>
+            </head><body></body></html>"#;
+
+        let entries = parse(Format::Trunk, content).unwrap();
+        assert_eq!(
+            entries.keys().collect::<Vec<_>>(),
+            ["app.js", "app_bg.wasm", "css/styles.css"]
+        );
+        assert_eq!(
+            entries["app.js"],
+            Entry {
+                file: "/app-1d2e3f4a5b6c7d8e.js".to_string(),
+                css: vec!["/css/styles-9f3a0b1c2d3e4f50.css".to_string()],
+                preload: vec!["/app-1d2e3f4a5b6c7d8e_bg.wasm".to_string()],
+            }
+        );
+        assert_eq!(entries["app_bg.wasm"].file, "/app-1d2e3f4a5b6c7d8e_bg.wasm");
+    }
+
+    #[test]
+    fn unbuilt_trunk_page() {
+        let content = r#"<link data-trunk rel="rust" href="Cargo.toml">"#;
+        assert!(parse(Format::Trunk, content).is_err());
+        assert!(parse(Format::Trunk, "<html></html>").is_err());
+    }
+}
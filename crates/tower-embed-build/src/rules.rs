@@ -0,0 +1,242 @@
+//! Parsers of `_headers` and `_redirects` files, using the syntax of Netlify and Cloudflare Pages.
+
+use tower_embed_core::{pattern::Pattern, rules::is_redirect};
+
+/// Custom headers added to the responses of the requests matching a pattern.
+#[derive(Debug, PartialEq)]
+pub struct HeaderRule {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// A rule redirecting or rewriting the requests matching a pattern.
+#[derive(Debug, PartialEq)]
+pub struct RedirectRule {
+    pub from: String,
+    pub to: String,
+    pub status: u16,
+    pub force: bool,
+}
+
+/// An error occurred parsing a rules file, with the line number.
+#[derive(Debug)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Error {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Parses the content of a `_headers` file.
+///
+/// Each block starts with an unindented path pattern, followed by indented `Name: value` lines.
+/// Lines starting with `#` are comments.
+pub fn parse_headers(content: &str) -> Result<Vec<HeaderRule>, Error> {
+    let mut rules: Vec<HeaderRule> = Vec::new();
+    for (line, text) in lines(content) {
+        if !text.starts_with([' ', '\t']) {
+            let path = text.trim();
+            parse_pattern(line, path)?;
+            rules.push(HeaderRule {
+                path: path.to_string(),
+                headers: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(rule) = rules.last_mut() else {
+            return Err(Error::new(line, "header without a path"));
+        };
+        let Some((name, value)) = text.split_once(':') else {
+            return Err(Error::new(line, "expected `Name: value`"));
+        };
+        let name = name.trim().to_ascii_lowercase();
+        let value = value.trim();
+        if http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            return Err(Error::new(line, format!("invalid header name `{name}`")));
+        }
+        // the values are created at runtime with `HeaderValue::from_static`, accepting only visible
+        // ASCII characters and tabs
+        if !value
+            .bytes()
+            .all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+        {
+            return Err(Error::new(
+                line,
+                format!("invalid value of header `{name}`"),
+            ));
+        }
+        rule.headers.push((name, value.to_string()));
+    }
+    Ok(rules)
+}
+
+/// Parses the content of a `_redirects` file.
+///
+/// Each line is made of the path pattern, the target and an optional status code, `301` by
+/// default. The status code followed by `!` forces the rule, even when an asset exists at the
+/// requested path. Lines starting with `#` are comments.
+pub fn parse_redirects(content: &str) -> Result<Vec<RedirectRule>, Error> {
+    let mut rules = Vec::new();
+    for (line, text) in lines(content) {
+        let mut fields = text.split_whitespace();
+        let (Some(from), Some(to)) = (fields.next(), fields.next()) else {
+            return Err(Error::new(line, "expected `from to [status]`"));
+        };
+        let (status, force) = match fields.next() {
+            Some(status) => match status.strip_suffix('!') {
+                Some(status) => (status, true),
+                None => (status, false),
+            },
+            None => ("301", false),
+        };
+        if fields.next().is_some() {
+            return Err(Error::new(
+                line,
+                "query parameters and conditions are not supported",
+            ));
+        }
+
+        parse_pattern(line, from)?;
+        let status = status
+            .parse::<http::StatusCode>()
+            .map_err(|_| Error::new(line, format!("invalid status code `{status}`")))?;
+        if status.is_redirection() && !is_redirect(status.as_u16()) {
+            return Err(Error::new(
+                line,
+                format!("unsupported redirect status code `{status}`"),
+            ));
+        }
+        if !status.is_redirection() && !to.starts_with('/') {
+            return Err(Error::new(
+                line,
+                "rewrite target must be an absolute path, proxying is not supported",
+            ));
+        }
+
+        rules.push(RedirectRule {
+            from: from.to_string(),
+            to: to.to_string(),
+            status: status.as_u16(),
+            force,
+        });
+    }
+    Ok(rules)
+}
+
+/// Returns the non-empty lines that are not comments, with their number.
+fn lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, text)| (index + 1, text.trim_end()))
+        .filter(|(_, text)| !text.trim().is_empty() && !text.trim_start().starts_with('#'))
+}
+
+fn parse_pattern(line: usize, pattern: &str) -> Result<(), Error> {
+    Pattern::parse(pattern)
+        .map(|_| ())
+        .map_err(|err| Error::new(line, format!("invalid pattern `{pattern}`: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_file() {
+        let content = "\
+# comment
+/*
+  X-Frame-Options: DENY
+  Cache-Control: no-cache
+
+/assets/*
+  cache-control: public, max-age=31536000
+";
+        let rules = parse_headers(content).unwrap();
+        assert_eq!(
+            rules,
+            [
+                HeaderRule {
+                    path: "/*".to_string(),
+                    headers: vec![
+                        ("x-frame-options".to_string(), "DENY".to_string()),
+                        ("cache-control".to_string(), "no-cache".to_string()),
+                    ],
+                },
+                HeaderRule {
+                    path: "/assets/*".to_string(),
+                    headers: vec![(
+                        "cache-control".to_string(),
+                        "public, max-age=31536000".to_string()
+                    )],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_headers_file() {
+        assert_eq!(parse_headers("  X-Test: 1").unwrap_err().line, 1);
+        assert_eq!(parse_headers("/*\n  X-Test").unwrap_err().line, 2);
+        assert_eq!(parse_headers("/*\n  X Test: 1").unwrap_err().line, 2);
+        assert_eq!(parse_headers("assets/*\n").unwrap_err().line, 1);
+        assert_eq!(parse_headers("/*\n  X-Test: café").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn redirects_file() {
+        let content = "\
+# comment
+/old /new
+/blog/:year/* /posts/:year/:splat 302
+/app/* /app/index.html 200
+/api/* /404.html 404!
+";
+        let rules = parse_redirects(content).unwrap();
+        let rule = |from: &str, to: &str, status, force| RedirectRule {
+            from: from.to_string(),
+            to: to.to_string(),
+            status,
+            force,
+        };
+        assert_eq!(
+            rules,
+            [
+                rule("/old", "/new", 301, false),
+                rule("/blog/:year/*", "/posts/:year/:splat", 302, false),
+                rule("/app/*", "/app/index.html", 200, false),
+                rule("/api/*", "/404.html", 404, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_redirects_file() {
+        assert_eq!(parse_redirects("/old").unwrap_err().line, 1);
+        assert_eq!(parse_redirects("\n/old /new 30x").unwrap_err().line, 2);
+        assert_eq!(
+            parse_redirects("/old /new 301 Country=it")
+                .unwrap_err()
+                .line,
+            1
+        );
+        assert_eq!(
+            parse_redirects("/api/* https://example.com 200")
+                .unwrap_err()
+                .line,
+            1
+        );
+        assert_eq!(parse_redirects("old /new").unwrap_err().line, 1);
+        assert_eq!(parse_redirects("/old /new 304").unwrap_err().line, 1);
+        assert_eq!(parse_redirects("/old /new 300").unwrap_err().line, 1);
+    }
+}
@@ -0,0 +1,124 @@
+//! Compression of the embedded files stored in the binary.
+
+use tower_embed_core::headers::ContentEncoding;
+
+/// How the contents of the files are stored in the binary.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Storage {
+    /// The contents are stored as they are.
+    #[default]
+    Raw,
+    /// The contents are stored compressed with Zstandard.
+    Zstd,
+    /// The contents are stored compressed with Brotli.
+    Brotli,
+}
+
+impl Storage {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "raw" => Ok(Self::Raw),
+            "zstd" => Ok(Self::Zstd),
+            "brotli" => Ok(Self::Brotli),
+            _ => Err(format!(
+                "unknown storage `{value}`, expected `raw`, `zstd` or `brotli`"
+            )),
+        }
+    }
+
+    /// The encoding of the compressed contents.
+    pub fn content_encoding(self) -> Option<ContentEncoding> {
+        match self {
+            Self::Raw => None,
+            Self::Zstd => Some(ContentEncoding::Zstd),
+            Self::Brotli => Some(ContentEncoding::Brotli),
+        }
+    }
+
+    /// Returns true if the crate is built with the feature compressing the contents.
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Self::Raw => true,
+            Self::Zstd => cfg!(feature = "zstd"),
+            Self::Brotli => cfg!(feature = "brotli"),
+        }
+    }
+
+    /// The check of the feature of `tower-embed` decompressing the contents.
+    pub fn feature(self) -> Option<syn::Ident> {
+        match self {
+            Self::Raw => None,
+            Self::Zstd => Some(quote::format_ident!("zstd")),
+            Self::Brotli => Some(quote::format_ident!("brotli")),
+        }
+    }
+
+    /// Compresses the content with the highest level, returning `None` when the content is
+    /// stored raw or the compressed form is not smaller.
+    pub fn compress(self, content: &[u8]) -> Option<Vec<u8>> {
+        let compressed = match self {
+            Self::Raw => return None,
+            Self::Zstd => compress_zstd(content)?,
+            Self::Brotli => compress_brotli(content)?,
+        };
+        (compressed.len() < content.len()).then_some(compressed)
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn compress_zstd(content: &[u8]) -> Option<Vec<u8>> {
+    zstd::bulk::compress(content, 19).ok()
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress_zstd(_content: &[u8]) -> Option<Vec<u8>> {
+    unreachable!("the feature is checked when the storage is parsed")
+}
+
+#[cfg(feature = "brotli")]
+fn compress_brotli(content: &[u8]) -> Option<Vec<u8>> {
+    use std::io::Write;
+
+    let mut compressed = Vec::new();
+    let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+    writer.write_all(content).ok()?;
+    drop(writer);
+    Some(compressed)
+}
+
+#[cfg(not(feature = "brotli"))]
+fn compress_brotli(_content: &[u8]) -> Option<Vec<u8>> {
+    unreachable!("the feature is checked when the storage is parsed")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    const CONTENT: &[u8] = b"body { margin: 0; } body { margin: 0; } body { margin: 0; }";
+
+    #[test]
+    fn zstd_roundtrip() {
+        let compressed = Storage::Zstd.compress(CONTENT).unwrap();
+        assert_eq!(zstd::decode_all(compressed.as_slice()).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn brotli_roundtrip() {
+        let compressed = Storage::Brotli.compress(CONTENT).unwrap();
+        let mut content = Vec::new();
+        brotli::Decompressor::new(compressed.as_slice(), 4096)
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, CONTENT);
+    }
+
+    #[test]
+    fn incompressible_content() {
+        assert_eq!(Storage::Raw.compress(CONTENT), None);
+        assert_eq!(Storage::Zstd.compress(b"a"), None);
+        assert_eq!(Storage::Brotli.compress(b""), None);
+    }
+}
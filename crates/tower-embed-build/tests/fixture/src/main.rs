@@ -0,0 +1,11 @@
+use tower_embed::Embed;
+
+#[derive(Embed)]
+#[embed(folder = "assets", file = "single.txt", fingerprint = "css/*.css")]
+struct Assets;
+
+fn main() {
+    let packed = tower_embed::embed!("assets", packed = true);
+    let _ = (Assets::get("index.html"), Assets::url("css/style.css"));
+    let _ = std::hint::black_box(packed);
+}
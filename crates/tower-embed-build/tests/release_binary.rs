@@ -0,0 +1,37 @@
+//! Builds the release binary of a crate embedding its assets with the derive macro, and checks the
+//! paths of the sources found in the binary.
+
+use std::{path::Path, process::Command};
+
+#[test]
+fn no_absolute_paths_in_release_binary() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixture");
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fixture");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--release", "--quiet", "--manifest-path"])
+        .arg(fixture.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success(), "failed to build the fixture");
+
+    let binary = target_dir.join("release").join(format!(
+        "tower-embed-fixture{}",
+        std::env::consts::EXE_SUFFIX
+    ));
+    let binary = std::fs::read(binary).unwrap();
+    let contains = |needle: &[u8]| binary.windows(needle.len()).any(|window| window == needle);
+
+    for content in [
+        "embedded-index-page",
+        "embedded-style-sheet",
+        "embedded-single-file",
+    ] {
+        assert!(contains(content.as_bytes()), "`{content}` is not embedded");
+    }
+    let fixture = fixture.to_str().unwrap();
+    assert!(
+        !contains(fixture.as_bytes()),
+        "the binary contains the absolute path `{fixture}`"
+    );
+}
@@ -0,0 +1,213 @@
+use std::{borrow::Cow, fmt, marker::PhantomData};
+
+/// A typed handle to a file embedded by `E`, checked at compile time.
+///
+/// Handles are created with the `asset!` macro, a path that does not exist is a compile error.
+pub struct Asset<E> {
+    path: &'static str,
+    _embed: PhantomData<fn() -> E>,
+}
+
+impl<E> Asset<E> {
+    /// Creates a handle without checking that the file exists.
+    #[doc(hidden)]
+    pub const fn new_unchecked(path: &'static str) -> Self {
+        Self {
+            path,
+            _embed: PhantomData,
+        }
+    }
+
+    /// Finds the file at the path among the embedded ones, in constant context.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the message of the closest file if no file is found, `messages` has the same
+    /// length of `files`. The closest file is used only for the message, the path must match
+    /// exactly.
+    #[doc(hidden)]
+    pub const fn lookup(
+        path: &'static str,
+        files: &[&'static str],
+        messages: &[&'static str],
+    ) -> Self {
+        let mut path = path.as_bytes();
+        if let [b'/', rest @ ..] = path {
+            path = rest;
+        }
+
+        let mut i = 0;
+        while i < files.len() {
+            if bytes_eq(path, files[i].as_bytes()) {
+                return Self::new_unchecked(files[i]);
+            }
+            i += 1;
+        }
+
+        let mut closest = None;
+        let mut min_distance = usize::MAX;
+        let mut i = 0;
+        while i < files.len() {
+            let distance = edit_distance(path, files[i].as_bytes());
+            if distance < min_distance {
+                min_distance = distance;
+                closest = Some(i);
+            }
+            i += 1;
+        }
+
+        match closest {
+            Some(i) => panic!("{}", messages[i]),
+            None => panic!("asset not found"),
+        }
+    }
+
+    /// Returns the path of the file.
+    pub const fn path(&self) -> &'static str {
+        self.path
+    }
+}
+
+impl<E> Clone for Asset<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for Asset<E> {}
+
+impl<E> PartialEq for Asset<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl<E> Eq for Asset<E> {}
+
+impl<E> fmt::Debug for Asset<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Asset").field(&self.path).finish()
+    }
+}
+
+impl<E> fmt::Display for Asset<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.path)
+    }
+}
+
+/// A path to a file embedded by `E`, either a string or a typed [`Asset`].
+pub trait IntoAssetPath<E> {
+    /// Converts the value into the path of the file.
+    fn into_asset_path(self) -> Cow<'static, str>;
+}
+
+impl<E> IntoAssetPath<E> for Asset<E> {
+    fn into_asset_path(self) -> Cow<'static, str> {
+        Cow::Borrowed(self.path)
+    }
+}
+
+impl<E> IntoAssetPath<E> for &str {
+    fn into_asset_path(self) -> Cow<'static, str> {
+        Cow::Owned(self.to_string())
+    }
+}
+
+impl<E> IntoAssetPath<E> for String {
+    fn into_asset_path(self) -> Cow<'static, str> {
+        Cow::Owned(self)
+    }
+}
+
+/// Returns true if the byte strings are equal, in constant context.
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// The Levenshtein distance between two strings, only the first bytes are compared.
+const fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    const MAX_LEN: usize = 256;
+
+    let n = if a.len() < MAX_LEN { a.len() } else { MAX_LEN };
+    let m = if b.len() < MAX_LEN { b.len() } else { MAX_LEN };
+
+    let mut row = [0; MAX_LEN + 1];
+    let mut j = 0;
+    while j <= m {
+        row[j] = j;
+        j += 1;
+    }
+
+    let mut i = 1;
+    while i <= n {
+        let mut diagonal = row[0];
+        row[0] = i;
+        let mut j = 1;
+        while j <= m {
+            let substitution = diagonal + (a[i - 1] != b[j - 1]) as usize;
+            let deletion = row[j] + 1;
+            let insertion = row[j - 1] + 1;
+            diagonal = row[j];
+
+            let mut min = substitution;
+            if deletion < min {
+                min = deletion;
+            }
+            if insertion < min {
+                min = insertion;
+            }
+            row[j] = min;
+            j += 1;
+        }
+        i += 1;
+    }
+    row[m]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: &[&str] = &["css/site.css", "index.html"];
+    const MESSAGES: &[&str] = &["did you mean `css/site.css`?", "did you mean `index.html`?"];
+
+    #[test]
+    fn lookup_asset() {
+        const ASSET: Asset<()> = Asset::lookup("/css/site.css", FILES, MESSAGES);
+        assert_eq!(ASSET.path(), "css/site.css");
+    }
+
+    #[test]
+    fn lookup_long_paths() {
+        let prefix = "a/".repeat(200);
+        let first = format!("{prefix}first.js");
+        let second = format!("{prefix}second.js");
+        let files = [first.leak() as &'static str, second.leak()];
+        let asset = Asset::<()>::lookup(files[1], &files, &["", ""]);
+        assert_eq!(asset.path(), files[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "did you mean `css/site.css`?")]
+    fn suggest_closest_asset() {
+        Asset::<()>::lookup("css/sites.css", FILES, MESSAGES);
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance(b"kitten", b"sitting"), 3);
+        assert_eq!(edit_distance(b"", b"abc"), 3);
+        assert_eq!(edit_distance(b"abc", b"abc"), 0);
+    }
+}
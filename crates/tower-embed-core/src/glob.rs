@@ -0,0 +1,208 @@
+//! Glob patterns matching relative file paths, like `**/*.{js,css}`.
+//!
+//! The supported syntax is:
+//!
+//! - `?` matches any character except `/`;
+//! - `*` matches any sequence of characters except `/`;
+//! - `**` as a whole segment matches any sequence of segments, including none;
+//! - `{a,b}` matches any of the comma separated alternatives, which cannot be nested.
+
+use std::fmt;
+
+/// A glob pattern matching relative file paths.
+#[derive(Clone, Debug)]
+pub struct Glob {
+    source: String,
+    alternatives: Vec<Vec<Token>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Char(char),
+    AnyChar,
+    AnySequence,
+    /// `**/` matching zero or more directories.
+    AnyDirs,
+    /// A trailing `**` matching the rest of the path.
+    AnyPath,
+}
+
+/// An error occurred parsing a glob pattern.
+#[derive(Debug)]
+pub struct GlobError(&'static str);
+
+impl fmt::Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for GlobError {}
+
+impl Glob {
+    /// Parses a glob pattern.
+    pub fn parse(glob: &str) -> Result<Self, GlobError> {
+        let alternatives = expand_braces(glob)?
+            .iter()
+            .map(|pattern| tokenize(pattern))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            source: glob.to_string(),
+            alternatives,
+        })
+    }
+
+    /// Returns true if the relative path matches the pattern.
+    ///
+    /// The leading slash of the path is ignored.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = path.trim_start_matches('/').chars().collect::<Vec<_>>();
+        self.alternatives
+            .iter()
+            .any(|tokens| matches_tokens(tokens, &path))
+    }
+
+    /// Returns the source of the pattern.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Expands the braces of the glob into the list of patterns without braces.
+fn expand_braces(glob: &str) -> Result<Vec<String>, GlobError> {
+    let Some(start) = glob.find('{') else {
+        if glob.contains('}') {
+            return Err(GlobError("unmatched '}'"));
+        }
+        return Ok(vec![glob.to_string()]);
+    };
+    let Some(len) = glob[start..].find('}') else {
+        return Err(GlobError("unmatched '{'"));
+    };
+    let (prefix, alternatives, suffix) = (
+        &glob[..start],
+        &glob[start + 1..start + len],
+        &glob[start + len + 1..],
+    );
+    if prefix.contains('}') {
+        return Err(GlobError("unmatched '}'"));
+    }
+    if alternatives.contains('{') {
+        return Err(GlobError("nested braces are not supported"));
+    }
+
+    let suffixes = expand_braces(suffix)?;
+    Ok(alternatives
+        .split(',')
+        .flat_map(|alternative| {
+            suffixes
+                .iter()
+                .map(move |suffix| format!("{prefix}{alternative}{suffix}"))
+        })
+        .collect())
+}
+
+fn tokenize(pattern: &str) -> Result<Vec<Token>, GlobError> {
+    let pattern = pattern.trim_start_matches('/');
+    let segments = pattern.split('/').collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let last = index + 1 == segments.len();
+        if *segment == "**" {
+            tokens.push(if last { Token::AnyPath } else { Token::AnyDirs });
+            continue;
+        }
+        if segment.contains("**") {
+            return Err(GlobError("'**' must be a whole segment"));
+        }
+
+        tokens.extend(segment.chars().map(|c| match c {
+            '?' => Token::AnyChar,
+            '*' => Token::AnySequence,
+            c => Token::Char(c),
+        }));
+        if !last {
+            tokens.push(Token::Char('/'));
+        }
+    }
+    Ok(tokens)
+}
+
+fn matches_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return path.is_empty();
+    };
+
+    match token {
+        Token::Char(c) => path.first() == Some(c) && matches_tokens(rest, &path[1..]),
+        Token::AnyChar => {
+            path.first().is_some_and(|c| *c != '/') && matches_tokens(rest, &path[1..])
+        }
+        Token::AnySequence => {
+            let len = path.iter().position(|c| *c == '/').unwrap_or(path.len());
+            (0..=len).any(|skip| matches_tokens(rest, &path[skip..]))
+        }
+        Token::AnyDirs => {
+            matches_tokens(rest, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '/')
+                    .any(|(index, _)| matches_tokens(rest, &path[index + 1..]))
+        }
+        Token::AnyPath => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_globs() {
+        assert!(Glob::parse("*.{js,css").is_err());
+        assert!(Glob::parse("*.js}").is_err());
+        assert!(Glob::parse("*.{js,{css,png}}").is_err());
+        assert!(Glob::parse("assets/**.js").is_err());
+    }
+
+    #[test]
+    fn match_wildcards() {
+        let glob = Glob::parse("*.css").unwrap();
+        assert!(glob.matches("style.css"));
+        assert!(glob.matches("/style.css"));
+        assert!(!glob.matches("css/style.css"));
+        assert!(!glob.matches("style.js"));
+
+        let glob = Glob::parse("img/?.png").unwrap();
+        assert!(glob.matches("img/a.png"));
+        assert!(!glob.matches("img/ab.png"));
+    }
+
+    #[test]
+    fn match_any_dirs() {
+        let glob = Glob::parse("**/*.js").unwrap();
+        assert!(glob.matches("app.js"));
+        assert!(glob.matches("assets/js/app.js"));
+        assert!(!glob.matches("app.css"));
+
+        let glob = Glob::parse("assets/**").unwrap();
+        assert!(glob.matches("assets/a/b.png"));
+        assert!(!glob.matches("other/a.png"));
+    }
+
+    #[test]
+    fn match_alternatives() {
+        let glob = Glob::parse("**/*.{js,css}").unwrap();
+        assert!(glob.matches("app.js"));
+        assert!(glob.matches("css/style.css"));
+        assert!(!glob.matches("index.html"));
+    }
+}
@@ -0,0 +1,101 @@
+//! Hash algorithms computing the entity tags of contents.
+//!
+//! The cryptographic algorithms are enabled by the `sha256` and `blake3` features.
+
+/// A hash algorithm computing the entity tags of contents, see [`etag_with`](crate::etag_with).
+pub trait ETagHasher {
+    /// Returns the hash of the content, as a string of ASCII characters.
+    fn hash(content: &[u8]) -> String;
+}
+
+/// The strength of an entity tag.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strength {
+    /// Strong entity tags change when the content changes in any way.
+    #[default]
+    Strong,
+    /// Weak entity tags are shared by semantically equivalent contents, like differently encoded
+    /// variants.
+    Weak,
+}
+
+/// The non-cryptographic 64-bit [rapidhash](https://github.com/Nicoshev/rapidhash).
+#[derive(Clone, Copy, Debug)]
+pub struct RapidHash;
+
+impl ETagHasher for RapidHash {
+    fn hash(content: &[u8]) -> String {
+        use std::hash::Hasher;
+
+        let mut hasher = rapidhash::fast::RapidHasher::default_const();
+        hasher.write(content);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// The SHA-256 cryptographic hash.
+#[cfg(feature = "sha256")]
+#[derive(Clone, Copy, Debug)]
+pub struct Sha256;
+
+#[cfg(feature = "sha256")]
+impl ETagHasher for Sha256 {
+    fn hash(content: &[u8]) -> String {
+        use sha2::Digest;
+
+        hex(&sha2::Sha256::digest(content))
+    }
+}
+
+/// The BLAKE3 cryptographic hash.
+#[cfg(feature = "blake3")]
+#[derive(Clone, Copy, Debug)]
+pub struct Blake3;
+
+#[cfg(feature = "blake3")]
+impl ETagHasher for Blake3 {
+    fn hash(content: &[u8]) -> String {
+        hex(blake3::hash(content).as_bytes())
+    }
+}
+
+#[cfg(any(feature = "sha256", feature = "blake3"))]
+fn hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+
+    bytes
+        .iter()
+        .fold(String::with_capacity(2 * bytes.len()), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rapidhash() {
+        assert_eq!(RapidHash::hash(b"hello").len(), 16);
+        assert_ne!(RapidHash::hash(b"hello"), RapidHash::hash(b"world"));
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn sha256() {
+        assert_eq!(
+            Sha256::hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3() {
+        assert_eq!(
+            Blake3::hash(b""),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+    }
+}
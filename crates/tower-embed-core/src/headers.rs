@@ -3,10 +3,14 @@
 //! [`http`]: https://docs.rs/http
 
 pub use self::{
+    accept::Accept, accept_encoding::AcceptEncoding, content_encoding::ContentEncoding,
     content_type::ContentType, etag::ETag, if_modified_since::IfModifiedSince,
     if_none_match::IfNoneMatch, last_modified::LastModified,
 };
 
+mod accept;
+mod accept_encoding;
+mod content_encoding;
 mod content_type;
 mod etag;
 mod if_modified_since;
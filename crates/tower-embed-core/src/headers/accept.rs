@@ -0,0 +1,160 @@
+/// `Accept` header.
+pub struct Accept {
+    value: http::HeaderValue,
+    ranges: Vec<MediaRange>,
+}
+
+/// A media range with its quality value, expressed in thousandths.
+struct MediaRange {
+    mime: mime::Mime,
+    quality: u16,
+}
+
+impl Accept {
+    /// Validates and creates an [`Accept`] from a HeaderValue.
+    fn from_header_value(value: &http::HeaderValue) -> Option<Self> {
+        let value_str = value.to_str().ok()?;
+        let ranges = value_str
+            .split(',')
+            .map(str::trim)
+            .filter(|range| !range.is_empty())
+            .map(MediaRange::parse)
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            value: value.clone(),
+            ranges,
+        })
+    }
+
+    /// Returns the media type preferred by the client among the available ones.
+    ///
+    /// Ties are resolved in favour of the media type that comes first in `available`.
+    pub fn negotiate<'a>(&self, available: &'a [mime::Mime]) -> Option<&'a mime::Mime> {
+        let mut preferred: Option<(&mime::Mime, u16)> = None;
+        for mime in available {
+            let quality = self.quality(mime);
+            if quality == 0 {
+                continue;
+            }
+            if preferred.is_none_or(|(_, best)| quality > best) {
+                preferred = Some((mime, quality));
+            }
+        }
+        preferred.map(|(mime, _)| mime)
+    }
+
+    /// Returns the quality of the most specific media range matching the media type.
+    fn quality(&self, mime: &mime::Mime) -> u16 {
+        self.ranges
+            .iter()
+            .filter_map(|range| range.specificity(mime).map(|s| (s, range.quality)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, quality)| quality)
+            .unwrap_or(0)
+    }
+}
+
+impl MediaRange {
+    fn parse(range: &str) -> Option<Self> {
+        let mime: mime::Mime = range.parse().ok()?;
+        let quality = match mime.get_param("q") {
+            Some(q) => parse_quality(q.as_str())?,
+            None => 1000,
+        };
+        Some(Self { mime, quality })
+    }
+
+    /// Returns how specific the range is if it matches the media type.
+    fn specificity(&self, mime: &mime::Mime) -> Option<u8> {
+        if self.mime.type_() == mime::STAR {
+            Some(0)
+        } else if self.mime.type_() != mime.type_() {
+            None
+        } else if self.mime.subtype() == mime::STAR {
+            Some(1)
+        } else if self.mime.subtype() == mime.subtype() {
+            Some(2)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses a quality value (`qvalue` in RFC 9110) as thousandths.
+pub(super) fn parse_quality(value: &str) -> Option<u16> {
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if fraction.len() > 3 || !fraction.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let fraction = format!("{fraction:0<3}").parse::<u16>().ok()?;
+    match integer {
+        "0" => Some(fraction),
+        "1" if fraction == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+impl super::Header for Accept {
+    fn header_name() -> http::HeaderName {
+        http::header::ACCEPT
+    }
+
+    fn decode(value: &http::HeaderValue) -> Option<Self> {
+        Self::from_header_value(value)
+    }
+
+    fn encode(self) -> http::HeaderValue {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &'static str) -> Accept {
+        let header_value = http::HeaderValue::from_static(value);
+        Accept::from_header_value(&header_value).unwrap()
+    }
+
+    #[test]
+    fn invalid_quality() {
+        let header_value = http::HeaderValue::from_static("text/html;q=2");
+        assert!(Accept::from_header_value(&header_value).is_none());
+
+        let header_value = http::HeaderValue::from_static("text/html;q=0.1234");
+        assert!(Accept::from_header_value(&header_value).is_none());
+    }
+
+    #[test]
+    fn negotiate_by_quality() {
+        let available = [mime::TEXT_HTML, mime::APPLICATION_JSON];
+
+        let accept = parse("text/html;q=0.5, application/json");
+        assert_eq!(accept.negotiate(&available), Some(&mime::APPLICATION_JSON));
+
+        let accept = parse("text/html, application/json;q=0.9");
+        assert_eq!(accept.negotiate(&available), Some(&mime::TEXT_HTML));
+    }
+
+    #[test]
+    fn negotiate_by_specificity() {
+        let available = [mime::TEXT_HTML, mime::APPLICATION_JSON];
+
+        let accept = parse("*/*;q=0.1, application/*");
+        assert_eq!(accept.negotiate(&available), Some(&mime::APPLICATION_JSON));
+
+        let accept = parse("text/*, text/html;q=0");
+        assert_eq!(accept.negotiate(&available), None);
+    }
+
+    #[test]
+    fn negotiate_ties() {
+        let available = [mime::TEXT_HTML, mime::APPLICATION_JSON];
+
+        let accept = parse("*/*");
+        assert_eq!(accept.negotiate(&available), Some(&mime::TEXT_HTML));
+    }
+}
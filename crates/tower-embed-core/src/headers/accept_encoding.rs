@@ -0,0 +1,89 @@
+use super::{ContentEncoding, accept::parse_quality};
+
+/// `Accept-Encoding` header.
+pub struct AcceptEncoding {
+    value: http::HeaderValue,
+    codings: Vec<(String, u16)>,
+}
+
+impl AcceptEncoding {
+    /// Validates and creates an [`AcceptEncoding`] from a HeaderValue.
+    fn from_header_value(value: &http::HeaderValue) -> Option<Self> {
+        let value_str = value.to_str().ok()?;
+        let codings = value_str
+            .split(',')
+            .map(str::trim)
+            .filter(|coding| !coding.is_empty())
+            .map(|coding| {
+                let (name, params) = coding.split_once(';').unwrap_or((coding, ""));
+                let quality = match params.trim().strip_prefix("q=") {
+                    Some(q) => parse_quality(q.trim())?,
+                    None if params.trim().is_empty() => 1000,
+                    None => return None,
+                };
+                Some((name.trim().to_ascii_lowercase(), quality))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            value: value.clone(),
+            codings,
+        })
+    }
+
+    /// Returns true if the client accepts the content coding.
+    pub fn accepts(&self, encoding: ContentEncoding) -> bool {
+        let quality = |name: &str| {
+            self.codings
+                .iter()
+                .find(|(coding, _)| coding == name)
+                .map(|(_, quality)| *quality)
+        };
+        quality(encoding.as_str())
+            .or_else(|| quality("*"))
+            .is_some_and(|quality| quality > 0)
+    }
+}
+
+impl super::Header for AcceptEncoding {
+    fn header_name() -> http::HeaderName {
+        http::header::ACCEPT_ENCODING
+    }
+
+    fn decode(value: &http::HeaderValue) -> Option<Self> {
+        Self::from_header_value(value)
+    }
+
+    fn encode(self) -> http::HeaderValue {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &'static str) -> AcceptEncoding {
+        let header_value = http::HeaderValue::from_static(value);
+        AcceptEncoding::from_header_value(&header_value).unwrap()
+    }
+
+    #[test]
+    fn accepts_codings() {
+        let accept_encoding = parse("gzip, br;q=0.8, zstd;q=0");
+        assert!(accept_encoding.accepts(ContentEncoding::Brotli));
+        assert!(!accept_encoding.accepts(ContentEncoding::Zstd));
+
+        let accept_encoding = parse("*;q=0.5, br;q=0");
+        assert!(accept_encoding.accepts(ContentEncoding::Zstd));
+        assert!(!accept_encoding.accepts(ContentEncoding::Brotli));
+
+        assert!(!parse("identity").accepts(ContentEncoding::Zstd));
+    }
+
+    #[test]
+    fn invalid_quality() {
+        let header_value = http::HeaderValue::from_static("br;q=2");
+        assert!(AcceptEncoding::from_header_value(&header_value).is_none());
+    }
+}
@@ -0,0 +1,36 @@
+/// `Content-Encoding` header, limited to the codings used to store embedded assets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// Brotli compressed data format, RFC 7932.
+    Brotli,
+    /// Zstandard compressed data format, RFC 8878.
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// Returns the name of the content coding.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+impl super::Header for ContentEncoding {
+    fn header_name() -> http::HeaderName {
+        http::header::CONTENT_ENCODING
+    }
+
+    fn decode(value: &http::HeaderValue) -> Option<Self> {
+        match value.to_str().ok()?.trim() {
+            "br" => Some(Self::Brotli),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    fn encode(self) -> http::HeaderValue {
+        http::HeaderValue::from_static(self.as_str())
+    }
+}
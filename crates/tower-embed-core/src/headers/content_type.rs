@@ -7,6 +7,11 @@ impl ContentType {
     pub const fn octet_stream() -> Self {
         ContentType(mime::APPLICATION_OCTET_STREAM)
     }
+
+    /// Parse a `Content-Type` header from a media type, like `application/json`.
+    pub fn parse(value: &str) -> Option<Self> {
+        value.parse().ok().map(ContentType)
+    }
 }
 
 impl super::Header for ContentType {
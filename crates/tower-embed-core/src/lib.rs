@@ -1,6 +1,7 @@
 //! Core functionalities of tower-embed.
 
 use std::{
+    borrow::Cow,
     error::Error,
     pin::Pin,
     task::{Context, Poll, ready},
@@ -10,13 +11,79 @@ use bytes::Bytes;
 use futures_core::{Stream, stream::BoxStream};
 use http_body::Frame;
 
+pub use self::asset::{Asset, IntoAssetPath};
+
+mod asset;
+pub mod glob;
+pub mod hasher;
 pub mod headers;
 pub mod http;
+pub mod manifest;
+pub mod pack;
+pub mod pattern;
+pub mod rules;
+pub mod substitute;
 
 /// A trait used to access to binary assets in a directory.
 pub trait Embed {
     /// Get an embedded asset by its path.
     fn get(path: &str) -> impl Future<Output = std::io::Result<Embedded>> + Send + 'static;
+
+    /// Get the path of the index file if the given path is a directory.
+    ///
+    /// The default implementation never finds an index file, so that no directory is detected.
+    fn index(path: &str) -> Option<Cow<'static, str>> {
+        let _ = path;
+        None
+    }
+
+    /// Get the path of the file served at the given URL without extension.
+    ///
+    /// The default implementation never finds a file, so that clean URLs are not supported.
+    fn clean_url(path: &str) -> Option<Cow<'static, str>> {
+        let _ = path;
+        None
+    }
+
+    /// Get the rules adding custom headers to responses, in order of evaluation.
+    ///
+    /// The default implementation has no rules.
+    fn header_rules() -> &'static [rules::HeaderRule] {
+        &[]
+    }
+
+    /// Get the rules redirecting or rewriting requests, in order of evaluation.
+    ///
+    /// The default implementation has no rules.
+    fn redirect_rules() -> &'static [rules::RedirectRule] {
+        &[]
+    }
+
+    /// Get the path of the not found page closest to the given path.
+    ///
+    /// The page is searched in the directory of the path first, and then in its ancestors up to
+    /// the root. The default implementation never finds a page.
+    fn nearest_not_found(path: &str) -> Option<Cow<'static, str>> {
+        let _ = path;
+        None
+    }
+
+    /// Get the `Content-Security-Policy` header of the page at the given path, allowing the
+    /// inline scripts and styles of the page with their hashes.
+    ///
+    /// The default implementation has no policy.
+    fn content_security_policy(path: &str) -> Option<&'static str> {
+        let _ = path;
+        None
+    }
+
+    /// Get the entity tag of a content known only at runtime, like a transformed page, computed
+    /// with the hash algorithm of the embedded assets.
+    ///
+    /// The default implementation uses [`RapidHash`](hasher::RapidHash).
+    fn etag(content: &[u8], strength: hasher::Strength) -> headers::ETag {
+        etag_with::<hasher::RapidHash>(content, strength)
+    }
 }
 
 /// An embedded binary asset.
@@ -36,7 +103,12 @@ pub struct Content(BoxStream<'static, Result<Bytes, BoxError>>);
 impl Content {
     /// Creates a [`Content`] from a static slice of bytes.
     pub fn from_static(bytes: &'static [u8]) -> Self {
-        Self(Box::pin(StaticContent::new(bytes)))
+        Self::from_bytes(Bytes::from_static(bytes))
+    }
+
+    /// Creates a [`Content`] from bytes.
+    pub fn from_bytes(bytes: impl Into<Bytes>) -> Self {
+        Self(Box::pin(BytesContent::new(bytes.into())))
     }
 
     /// Creates a [`Content`] from a stream of frames.
@@ -57,22 +129,19 @@ impl Stream for Content {
     }
 }
 
-struct StaticContent(Option<&'static [u8]>);
+struct BytesContent(Option<Bytes>);
 
-impl StaticContent {
-    pub fn new(bytes: &'static [u8]) -> Self {
+impl BytesContent {
+    pub fn new(bytes: Bytes) -> Self {
         Self(Some(bytes))
     }
 }
 
-impl Stream for StaticContent {
+impl Stream for BytesContent {
     type Item = Result<Bytes, BoxError>;
 
     fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.0
-            .take()
-            .map(|bytes| Ok(Bytes::from_static(bytes)))
-            .into()
+        self.0.take().map(Ok).into()
     }
 }
 
@@ -97,7 +166,19 @@ where
 }
 
 /// Metadata associated with an embedded asset.
+///
+/// New fields may be added in minor releases, the metadata is created with [`Metadata::new`].
+///
+/// # Example
+///
+/// ```
+/// # use tower_embed_core::{Metadata, headers};
+/// let metadata = Metadata::new(headers::ContentType(mime::TEXT_HTML))
+///     .etag(headers::ETag::new("abc"))
+///     .immutable(true);
+/// ```
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Metadata {
     /// MIME type of the resource.
     pub content_type: headers::ContentType,
@@ -105,6 +186,48 @@ pub struct Metadata {
     pub etag: Option<headers::ETag>,
     /// The date and time when the resource was modified.
     pub last_modified: Option<headers::LastModified>,
+    /// True if the content at the URL of the resource never changes, like fingerprinted files.
+    pub immutable: bool,
+    /// The coding of the content, if it is stored compressed.
+    pub content_encoding: Option<headers::ContentEncoding>,
+}
+
+impl Metadata {
+    /// Create the metadata of a resource with the content type, without validators, not immutable
+    /// and not compressed.
+    pub fn new(content_type: headers::ContentType) -> Self {
+        Self {
+            content_type,
+            etag: None,
+            last_modified: None,
+            immutable: false,
+            content_encoding: None,
+        }
+    }
+
+    /// Set the entity tag of the resource.
+    pub fn etag(mut self, etag: Option<headers::ETag>) -> Self {
+        self.etag = etag;
+        self
+    }
+
+    /// Set the modification time of the resource.
+    pub fn last_modified(mut self, last_modified: Option<headers::LastModified>) -> Self {
+        self.last_modified = last_modified;
+        self
+    }
+
+    /// Set whether the content at the URL of the resource never changes.
+    pub fn immutable(mut self, immutable: bool) -> Self {
+        self.immutable = immutable;
+        self
+    }
+
+    /// Set the coding of the content, if it is stored compressed.
+    pub fn content_encoding(mut self, content_encoding: Option<headers::ContentEncoding>) -> Self {
+        self.content_encoding = content_encoding;
+        self
+    }
 }
 
 /// Returns the last modification time of file.
@@ -123,15 +246,22 @@ pub fn content_type(path: &std::path::Path) -> headers::ContentType {
 }
 
 /// Returns the unique identifier tag of the content.
+///
+/// The tag is a strong entity tag computed with [`RapidHash`](hasher::RapidHash), see
+/// [`etag_with`] to use a different algorithm.
 pub fn etag(content: &[u8]) -> headers::ETag {
-    use std::hash::Hasher;
-
-    let hash: u64 = {
-        let mut hasher = rapidhash::fast::RapidHasher::default_const();
-        hasher.write(content);
-        hasher.finish()
-    };
+    etag_with::<hasher::RapidHash>(content, hasher::Strength::Strong)
+}
 
-    let etag = format!("{:016x}", hash);
-    headers::ETag::new(&etag).unwrap()
+/// Returns the identifier tag of the content, computed with the given hash algorithm.
+pub fn etag_with<H: hasher::ETagHasher>(
+    content: &[u8],
+    strength: hasher::Strength,
+) -> headers::ETag {
+    let hash = H::hash(content);
+    match strength {
+        hasher::Strength::Strong => headers::ETag::new(&hash),
+        hasher::Strength::Weak => headers::ETag::weak(&hash),
+    }
+    .expect("hash is a valid ASCII string")
 }
@@ -0,0 +1,17 @@
+//! Entry points of build manifests, generated by bundlers like Vite, webpack or Trunk.
+
+/// An entry point of a build manifest, with the paths of the files to load in HTML pages.
+///
+/// The paths are root-relative URLs, like `/assets/main.js`, of the files in the embedded folder.
+/// They must be prefixed with the path where the service is mounted, if it is not the root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The path of the built file, to load with a `<script type="module">` tag.
+    pub file: &'static str,
+    /// The paths of the stylesheets imported by the entry point and its dependencies, to load
+    /// with `<link rel="stylesheet">` tags.
+    pub css: &'static [&'static str],
+    /// The paths of the chunks imported by the entry point, to load with
+    /// `<link rel="modulepreload">` tags.
+    pub preload: &'static [&'static str],
+}
@@ -0,0 +1,305 @@
+//! Archive packing the embedded files in a single blob, with an index of the entries.
+//!
+//! The archive starts with the index, followed by the contents of the files. The integers are
+//! little endian, the offsets of the contents are relative to the end of the index.
+//!
+//! ```text
+//! archive  = "TEPK" version:u8 count:u32 entry{count} content*
+//! entry    = path:string (file | redirect)
+//! file     = 0:u8 offset:u64 len:u64 encoding:u8 immutable:u8 etag last_modified:u64
+//! etag     = 0:u8 | 1:u8 value:string | 2:u8 value:string   (none, strong, weak)
+//! redirect = 1:u8 target:string
+//! string   = len:u32 bytes
+//! ```
+//!
+//! A missing `Last-Modified` time is encoded as `u64::MAX`.
+
+use std::{collections::HashMap, path::Path};
+
+use crate::{Metadata, content_type, headers};
+
+const MAGIC: &[u8] = b"TEPK";
+const VERSION: u8 = 1;
+
+/// An entry of the archive.
+pub enum Entry {
+    /// A file, with its content.
+    File(&'static [u8], Metadata),
+    /// A directory, redirected to its index file.
+    Redirect(&'static str),
+}
+
+/// The metadata of a file written in the archive.
+#[derive(Clone, Debug, Default)]
+pub struct FileMetadata {
+    pub etag: Option<headers::ETag>,
+    /// The modification time, as seconds since the Unix epoch.
+    pub last_modified: Option<u64>,
+    pub immutable: bool,
+    pub content_encoding: Option<headers::ContentEncoding>,
+}
+
+/// The location of a content in the archive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContentRange {
+    offset: u64,
+    len: u64,
+}
+
+/// Writes an archive.
+#[derive(Default)]
+pub struct Writer {
+    index: Vec<u8>,
+    contents: Vec<u8>,
+    count: u32,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a content, returning its location to be shared by files.
+    pub fn add_content(&mut self, content: &[u8]) -> ContentRange {
+        let range = ContentRange {
+            offset: self.contents.len() as u64,
+            len: content.len() as u64,
+        };
+        self.contents.extend_from_slice(content);
+        range
+    }
+
+    /// Adds a file with a content already appended.
+    pub fn add_file(&mut self, path: &str, content: ContentRange, metadata: &FileMetadata) {
+        self.count += 1;
+        write_str(&mut self.index, path);
+        self.index.push(0);
+        self.index.extend_from_slice(&content.offset.to_le_bytes());
+        self.index.extend_from_slice(&content.len.to_le_bytes());
+        self.index.push(match metadata.content_encoding {
+            None => 0,
+            Some(headers::ContentEncoding::Brotli) => 1,
+            Some(headers::ContentEncoding::Zstd) => 2,
+        });
+        self.index.push(metadata.immutable as u8);
+        match &metadata.etag {
+            None => self.index.push(0),
+            Some(etag) => {
+                self.index.push(if etag.is_weak() { 2 } else { 1 });
+                write_str(&mut self.index, etag.value());
+            }
+        }
+        let last_modified = metadata.last_modified.unwrap_or(u64::MAX);
+        self.index.extend_from_slice(&last_modified.to_le_bytes());
+    }
+
+    /// Adds a directory redirected to its index file.
+    pub fn add_redirect(&mut self, path: &str, target: &str) {
+        self.count += 1;
+        write_str(&mut self.index, path);
+        self.index.push(1);
+        write_str(&mut self.index, target);
+    }
+
+    /// Returns the bytes of the archive.
+    pub fn finish(self) -> Vec<u8> {
+        let mut archive = Vec::with_capacity(9 + self.index.len() + self.contents.len());
+        archive.extend_from_slice(MAGIC);
+        archive.push(VERSION);
+        archive.extend_from_slice(&self.count.to_le_bytes());
+        archive.extend_from_slice(&self.index);
+        archive.extend_from_slice(&self.contents);
+        archive
+    }
+}
+
+fn write_str(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+/// Reads the entries of an archive, by path.
+///
+/// # Panics
+///
+/// Panics if the archive is malformed, it is generated by the derive macro.
+pub fn read(archive: &'static [u8]) -> HashMap<&'static str, Entry> {
+    let mut reader = Reader(archive);
+    assert!(
+        reader.bytes(MAGIC.len()) == MAGIC && reader.u8() == VERSION,
+        "invalid archive of embedded files"
+    );
+
+    let count = reader.u32() as usize;
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        let path = reader.str();
+        let entry = match reader.u8() {
+            0 => {
+                let offset = reader.u64() as usize;
+                let len = reader.u64() as usize;
+                let content_encoding = match reader.u8() {
+                    0 => None,
+                    1 => Some(headers::ContentEncoding::Brotli),
+                    2 => Some(headers::ContentEncoding::Zstd),
+                    _ => panic!("invalid archive of embedded files"),
+                };
+                let immutable = reader.u8() != 0;
+                let etag = match reader.u8() {
+                    0 => None,
+                    1 => headers::ETag::new(reader.str()),
+                    2 => headers::ETag::weak(reader.str()),
+                    _ => panic!("invalid archive of embedded files"),
+                };
+                let last_modified = match reader.u64() {
+                    u64::MAX => None,
+                    secs => headers::LastModified::from_unix_timestamp(secs),
+                };
+                let metadata = Metadata::new(content_type(Path::new(path)))
+                    .etag(etag)
+                    .last_modified(last_modified)
+                    .immutable(immutable)
+                    .content_encoding(content_encoding);
+                Pending::File(offset..offset + len, metadata)
+            }
+            1 => Pending::Redirect(reader.str()),
+            _ => panic!("invalid archive of embedded files"),
+        };
+        entries.push((path, entry));
+    }
+
+    // the offsets are relative to the end of the index
+    let contents = reader.0;
+    entries
+        .into_iter()
+        .map(|(path, entry)| {
+            let entry = match entry {
+                Pending::File(range, metadata) => Entry::File(&contents[range], metadata),
+                Pending::Redirect(target) => Entry::Redirect(target),
+            };
+            (path, entry)
+        })
+        .collect()
+}
+
+enum Pending {
+    File(std::ops::Range<usize>, Metadata),
+    Redirect(&'static str),
+}
+
+struct Reader(&'static [u8]);
+
+impl Reader {
+    fn bytes(&mut self, len: usize) -> &'static [u8] {
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        bytes
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.bytes(4).try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes(8).try_into().unwrap())
+    }
+
+    fn str(&mut self) -> &'static str {
+        let len = self.u32() as usize;
+        std::str::from_utf8(self.bytes(len)).expect("invalid archive of embedded files")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut writer = Writer::new();
+        let style = writer.add_content(b"body {}");
+        let page = writer.add_content(b"<h1>Hello</h1>");
+        let metadata = FileMetadata {
+            etag: headers::ETag::weak("abc"),
+            last_modified: Some(1700000000),
+            ..FileMetadata::default()
+        };
+        writer.add_file("style.css", style, &metadata);
+        writer.add_file(
+            "style.0123.css",
+            style,
+            &FileMetadata {
+                immutable: true,
+                ..metadata.clone()
+            },
+        );
+        writer.add_file(
+            "docs/index.html",
+            page,
+            &FileMetadata {
+                content_encoding: Some(headers::ContentEncoding::Zstd),
+                ..FileMetadata::default()
+            },
+        );
+        writer.add_redirect("docs", "docs/index.html");
+        let archive = writer.finish().leak();
+
+        let entries = read(archive);
+        assert_eq!(entries.len(), 4);
+
+        let Entry::File(content, metadata) = &entries["style.css"] else {
+            panic!("expected a file");
+        };
+        assert_eq!(*content, b"body {}");
+        assert_eq!(metadata.content_type.0, mime::TEXT_CSS);
+        assert!(metadata.etag.as_ref().unwrap().is_weak());
+        assert_eq!(metadata.etag.as_ref().unwrap().value(), "abc");
+        assert!(metadata.last_modified.is_some());
+        assert!(!metadata.immutable);
+
+        let Entry::File(content, metadata) = &entries["style.0123.css"] else {
+            panic!("expected a file");
+        };
+        assert_eq!(*content, b"body {}");
+        assert!(metadata.immutable);
+
+        let Entry::File(content, metadata) = &entries["docs/index.html"] else {
+            panic!("expected a file");
+        };
+        assert_eq!(*content, b"<h1>Hello</h1>");
+        assert!(metadata.etag.is_none());
+        assert!(metadata.last_modified.is_none());
+        assert_eq!(
+            metadata.content_encoding,
+            Some(headers::ContentEncoding::Zstd)
+        );
+
+        assert!(matches!(
+            entries["docs"],
+            Entry::Redirect("docs/index.html")
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid archive")]
+    fn invalid_archive() {
+        read(b"PK\x03\x04\x00");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid archive")]
+    fn unknown_content_encoding() {
+        let mut writer = Writer::new();
+        let content = writer.add_content(b"body {}");
+        writer.add_file("style.css", content, &FileMetadata::default());
+        let mut archive = writer.finish();
+        // the encoding follows the magic, version, count, path, kind, offset and length
+        let offset = MAGIC.len() + 1 + 4 + 4 + "style.css".len() + 1 + 8 + 8;
+        archive[offset] = 3;
+        read(archive.leak());
+    }
+}
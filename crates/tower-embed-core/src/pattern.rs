@@ -0,0 +1,249 @@
+//! Patterns matching request paths, like `/blog/:year/*`.
+//!
+//! A pattern is made of segments separated by `/`, each segment is either a literal, or a
+//! placeholder `:name` matching a whole segment. The last segment can be a splat `*`, matching the
+//! rest of the path. The values captured by a pattern can be substituted into a target path.
+
+use std::fmt;
+
+/// A pattern matching request paths.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    source: String,
+    segments: Vec<Segment>,
+    splat: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// An error occurred parsing a pattern.
+#[derive(Debug)]
+pub struct PatternError(&'static str);
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// The values captured by a pattern.
+#[derive(Debug, Default)]
+pub struct Captures<'a> {
+    placeholders: Vec<(&'a str, &'a str)>,
+    splat: Option<&'a str>,
+}
+
+impl Pattern {
+    /// Parses a pattern, it must start with `/`.
+    pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+        let Some(path) = pattern.strip_prefix('/') else {
+            return Err(PatternError("pattern must start with '/'"));
+        };
+
+        let mut segments = Vec::new();
+        let mut splat = false;
+        for segment in path.trim_end_matches('/').split('/') {
+            if splat {
+                return Err(PatternError("splat '*' must be the last segment"));
+            }
+
+            if segment == "*" {
+                splat = true;
+            } else if let Some(name) = segment.strip_prefix(':') {
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return Err(PatternError("invalid placeholder name"));
+                }
+                if name == "splat" {
+                    return Err(PatternError("placeholder name 'splat' is reserved"));
+                }
+                segments.push(Segment::Placeholder(name.to_string()));
+            } else if segment.contains('*') {
+                return Err(PatternError("splat '*' must be a whole segment"));
+            } else if !segment.is_empty() || path.is_empty() {
+                segments.push(Segment::Literal(segment.to_string()));
+            } else {
+                return Err(PatternError("empty segment"));
+            }
+        }
+
+        Ok(Self {
+            source: pattern.to_string(),
+            segments,
+            splat,
+        })
+    }
+
+    /// Returns the captured values if the path matches the pattern.
+    ///
+    /// The trailing slash of the path is ignored.
+    pub fn matches<'a>(&'a self, path: &'a str) -> Option<Captures<'a>> {
+        let path = path.strip_prefix('/')?;
+        let mut rest = Some(path.trim_end_matches('/'));
+        let mut captures = Captures::default();
+
+        for segment in &self.segments {
+            let (value, tail) = match rest?.split_once('/') {
+                Some((value, tail)) => (value, Some(tail)),
+                None => (rest?, None),
+            };
+            match segment {
+                Segment::Literal(literal) if literal == value => {}
+                Segment::Literal(_) => return None,
+                Segment::Placeholder(_) if value.is_empty() => return None,
+                Segment::Placeholder(name) => captures.placeholders.push((name, value)),
+            }
+            rest = tail;
+        }
+
+        if self.splat {
+            captures.splat = Some(rest.unwrap_or(""));
+        } else if rest.is_some() {
+            return None;
+        }
+        Some(captures)
+    }
+
+    /// Returns the source of the pattern.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Captures<'_> {
+    /// Substitutes the `:name` placeholders, and `:splat` for the splat, in the target.
+    ///
+    /// Placeholders that are not captured are left unchanged. If the target is a path, the leading
+    /// slashes of the result are collapsed, so a captured value like `/evil.com` cannot turn it
+    /// into a protocol-relative URL.
+    pub fn substitute(&self, target: &str) -> String {
+        let mut output = String::with_capacity(target.len());
+        let mut rest = target;
+        while let Some(start) = rest.find(':') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+
+            let len = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let name = &rest[..len];
+            match self.get(name) {
+                Some(value) => output.push_str(value),
+                None => {
+                    output.push(':');
+                    output.push_str(name);
+                }
+            }
+            rest = &rest[len..];
+        }
+        output.push_str(rest);
+
+        if target.starts_with('/') {
+            let path = output.trim_start_matches(['/', '\\']);
+            if path.len() + 1 != output.len() {
+                output = format!("/{path}");
+            }
+        }
+        output
+    }
+
+    /// Returns the value captured by the placeholder with the given name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        if name == "splat" {
+            return self.splat;
+        }
+        self.placeholders
+            .iter()
+            .find(|(placeholder, _)| *placeholder == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(Pattern::parse("img/*").is_err());
+        assert!(Pattern::parse("/img/*/x").is_err());
+        assert!(Pattern::parse("/img/*.png").is_err());
+        assert!(Pattern::parse("/img/:").is_err());
+        assert!(Pattern::parse("/img/:splat").is_err());
+        assert!(Pattern::parse("/img//x").is_err());
+    }
+
+    #[test]
+    fn match_literals() {
+        let pattern = Pattern::parse("/old/page").unwrap();
+        assert!(pattern.matches("/old/page").is_some());
+        assert!(pattern.matches("/old/page/").is_some());
+        assert!(pattern.matches("/old").is_none());
+        assert!(pattern.matches("/old/page/more").is_none());
+
+        let pattern = Pattern::parse("/").unwrap();
+        assert!(pattern.matches("/").is_some());
+        assert!(pattern.matches("/page").is_none());
+    }
+
+    #[test]
+    fn match_placeholders() {
+        let pattern = Pattern::parse("/blog/:year/:slug").unwrap();
+        let captures = pattern.matches("/blog/2024/hello").unwrap();
+        assert_eq!(captures.get("year"), Some("2024"));
+        assert_eq!(captures.get("slug"), Some("hello"));
+        assert!(pattern.matches("/blog/2024").is_none());
+        assert!(pattern.matches("/blog//hello").is_none());
+    }
+
+    #[test]
+    fn match_splat() {
+        let pattern = Pattern::parse("/img/*").unwrap();
+        assert_eq!(
+            pattern.matches("/img/a/b.png").unwrap().get("splat"),
+            Some("a/b.png")
+        );
+        assert_eq!(pattern.matches("/img/").unwrap().get("splat"), Some(""));
+        assert_eq!(pattern.matches("/img").unwrap().get("splat"), Some(""));
+        assert!(pattern.matches("/images/a.png").is_none());
+
+        let pattern = Pattern::parse("/*").unwrap();
+        assert_eq!(pattern.matches("/a/b").unwrap().get("splat"), Some("a/b"));
+    }
+
+    #[test]
+    fn substitute_captures() {
+        let pattern = Pattern::parse("/blog/:year/*").unwrap();
+        let captures = pattern.matches("/blog/2024/a/b").unwrap();
+        assert_eq!(
+            captures.substitute("/posts/:year/:splat"),
+            "/posts/2024/a/b"
+        );
+        assert_eq!(captures.substitute("/posts/:month"), "/posts/:month");
+        assert_eq!(
+            captures.substitute("https://example.com:8080/:year"),
+            "https://example.com:8080/2024"
+        );
+    }
+
+    #[test]
+    fn substitute_leading_slashes() {
+        let pattern = Pattern::parse("/old/*").unwrap();
+        let captures = pattern.matches("/old//evil.com/a//b").unwrap();
+        assert_eq!(captures.substitute("/:splat"), "/evil.com/a//b");
+        let captures = pattern.matches("/old/\\evil.com").unwrap();
+        assert_eq!(captures.substitute("/:splat"), "/evil.com");
+        assert_eq!(captures.substitute("https://:splat"), "https://\\evil.com");
+    }
+}
@@ -0,0 +1,37 @@
+//! Static rules attached to embedded assets.
+//!
+//! The rules are usually parsed at compile time from `_headers` and `_redirects` files, using the
+//! syntax of Netlify and Cloudflare Pages. Paths are [patterns] matching request paths.
+//!
+//! [patterns]: crate::pattern
+
+/// Custom headers added to the responses of the requests matching a pattern.
+#[derive(Clone, Copy, Debug)]
+pub struct HeaderRule {
+    /// The pattern matching request paths.
+    pub path: &'static str,
+    /// The custom headers as pairs of name and value.
+    pub headers: &'static [(&'static str, &'static str)],
+}
+
+/// A rule redirecting or rewriting the requests matching a pattern.
+#[derive(Clone, Copy, Debug)]
+pub struct RedirectRule {
+    /// The pattern matching request paths.
+    pub from: &'static str,
+    /// The target of the rule, placeholders captured by the pattern are substituted.
+    pub to: &'static str,
+    /// The status code of the response, a [redirect status code](is_redirect) redirects the
+    /// client to the target, any other status code serves the target in place of the requested
+    /// path.
+    pub status: u16,
+    /// If false the rule is applied only when no asset is found at the requested path.
+    pub force: bool,
+}
+
+/// Returns true if the status code redirects the client, `301`, `302`, `303`, `307` or `308`.
+///
+/// The other redirection status codes, `300` and `304`, are not valid in rules.
+pub fn is_redirect(status: u16) -> bool {
+    matches!(status, 301 | 302 | 303 | 307 | 308)
+}
@@ -0,0 +1,115 @@
+//! Substitution of the placeholders in text assets.
+//!
+//! A placeholder is the name of a variable, an identifier like `APP_VERSION`, enclosed in double
+//! braces `{{APP_VERSION}}` or in percent signs `%APP_VERSION%`. Spaces are allowed inside the
+//! braces. Any other text is left unchanged.
+
+use std::fmt;
+
+/// An error occurred substituting the placeholders.
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    /// The line of the placeholder, starting from 1
+    pub line: usize,
+    /// The name of the unknown variable
+    pub name: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown placeholder `{}` at line {}",
+            self.name, self.line
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Replaces the placeholders with the values of the variables, failing on the first placeholder
+/// of an unknown variable.
+pub fn substitute(content: &str, vars: &[(&str, &str)]) -> Result<String, Error> {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find(['{', '%']) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some((name, len)) = placeholder(rest) else {
+            let len = rest.chars().next().map_or(0, char::len_utf8);
+            output.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        };
+        match vars.iter().find(|(var, _)| *var == name) {
+            Some((_, value)) => output.push_str(value),
+            None => {
+                let offset = content.len() - rest.len();
+                return Err(Error {
+                    line: content[..offset].matches('\n').count() + 1,
+                    name: name.to_string(),
+                });
+            }
+        }
+        rest = &rest[len..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Returns the name and the length of the placeholder at the start of the text.
+fn placeholder(text: &str) -> Option<(&str, usize)> {
+    if let Some(inner) = text.strip_prefix("{{") {
+        let end = inner.find("}}")?;
+        let name = inner[..end].trim();
+        return is_identifier(name).then_some((name, end + 4));
+    }
+
+    let inner = text.strip_prefix('%')?;
+    let end = inner.find('%')?;
+    let name = &inner[..end];
+    is_identifier(name).then_some((name, end + 2))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARS: &[(&str, &str)] = &[("VERSION", "1.2.0"), ("API_URL", "https://api.test")];
+
+    #[test]
+    fn replace_placeholders() {
+        let content = "<meta version=\"{{VERSION}}\" api=\"%API_URL%\">{{ VERSION }}";
+        assert_eq!(
+            substitute(content, VARS).unwrap(),
+            "<meta version=\"1.2.0\" api=\"https://api.test\">1.2.0"
+        );
+    }
+
+    #[test]
+    fn keep_other_text() {
+        let content = "width: 50%; height: 100%; {{ 1 + 1 }} {not} %20 ünïcode {";
+        assert_eq!(substitute(content, VARS).unwrap(), content);
+    }
+
+    #[test]
+    fn unknown_placeholder() {
+        let content = "<html>\n<title>%TITLE%</title>";
+        assert_eq!(
+            substitute(content, VARS).unwrap_err(),
+            Error {
+                line: 2,
+                name: "TITLE".to_string()
+            }
+        );
+    }
+}
@@ -1,9 +1,3 @@
-use std::borrow::Cow;
-
-use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
-use quote::ToTokens;
-use tower_embed_core::headers;
-
 /// Derive the `Embed` trait for unit struct, embedding assets from a folder.
 ///
 /// ## Usage
@@ -11,285 +5,135 @@ use tower_embed_core::headers;
 /// Apply `#[derive(Embed)]` to a unit struct and specify the folder to embed using the
 /// `#[embed(folder = "...")]` attribute.
 ///
+/// Single files are embedded with `#[embed(file = "...")]`, relative to `CARGO_MANIFEST_DIR`, and
+/// served at their file name or at the path given by a following `path = "..."`, like
+/// `#[embed(file = "static/favicon.ico", path = "favicon.ico")]`. The attribute can be repeated
+/// and mixed with `folder`, a single file takes precedence over a file of the folder at the same
+/// path, while two single files at the same path are a compile error. The folder can be omitted
+/// when single files are embedded.
+///
+/// The generated code references the files relative to `CARGO_MANIFEST_DIR`, so release binaries
+/// do not contain the absolute paths of the sources. The debug backend reads the files at runtime
+/// from `env!("CARGO_MANIFEST_DIR")`, so debug binaries contain the absolute path of the package.
+///
+/// Virtual files are generated from compile-time expressions with `#[embed(virtual(path = "...",
+/// content = ...))]`, where the content is a `&'static str` expression like `concat!(...)` or
+/// `env!(...)`. The content type is guessed from the path, or specified with `content_type =
+/// "..."`. They are served like the other files, with entity tags computed on first use, and they
+/// take precedence over the files of the folder at the same path, while a single file or another
+/// virtual file at the same path is a compile error. They are not fingerprinted and have no
+/// Subresource Integrity metadata.
+///
 /// Optionally, specify the crate path with `#[embed(crate = path)]`. This is applicable when
 /// invoking re-exported derive from a public macro in a different crate.
 ///
 /// The name of file to serve as index for directories can be customized using #[embed(index =
-/// "...")], the default is "index.html".
+/// "...")], the default is "index.html". The attribute can be repeated to specify multiple
+/// candidates, the first one found in a directory is served.
+///
+/// The extensions that can be omitted from URLs, resolved by `Embed::clean_url`, are specified
+/// using `#[embed(extension = "...")]`, the default is "html". The attribute can be repeated, when
+/// multiple files share the same URL the first extension takes precedence. In release builds the
+/// URLs are resolved at compile time.
+///
+/// The name of the not found pages can be specified using `#[embed(not_found = "...")]`, then the
+/// page closest to the requested path is resolved by `Embed::nearest_not_found`. In release
+/// builds the closest page of each directory is computed at compile time.
+///
+/// Custom headers and redirect rules can be loaded from files in the embedded folder, using the
+/// syntax of Netlify and Cloudflare Pages, with `#[embed(headers_file = "_headers")]` and
+/// `#[embed(redirects_file = "_redirects")]`. The files are parsed at compile time, syntax errors
+/// are reported as compile errors, and they are not served.
+///
+/// The files matching the glob pattern specified with `#[embed(fingerprint = "...")]`, like
+/// `"**/*.{js,css}"`, are served also at a fingerprinted path containing their content hash, like
+/// `style.<hash>.css`, with immutable caching. The fingerprinted paths are resolved by the
+/// generated `url` method, in debug builds the paths are returned unchanged.
+///
+/// The entity tags of the files are computed at compile time, the hash algorithm is specified with
+/// `#[embed(etag = "...")]`, either `"rapidhash"`, `"sha256"` or `"blake3"`, the default is
+/// `"rapidhash"`. Weak entity tags are selected with `#[embed(etag_strength = "weak")]`. The
+/// entity tags of the virtual files and of the pages transformed at runtime are computed with the
+/// same algorithm, `"sha256"` and `"blake3"` require the feature of the same name of
+/// `tower-embed`.
+///
+/// The placeholders of the text files matching the glob pattern specified with
+/// `#[embed(substitute = "...")]` are replaced with the values of `#[embed(vars(NAME = ...))]`,
+/// either a string literal or `env!("...")` read at compile time. A placeholder is the name of a
+/// variable enclosed in double braces `{{NAME}}` or in percent signs `%NAME%`, a placeholder of an
+/// unknown variable is a compile error. The files are substituted before they are hashed, and the
+/// debug backend substitutes them on the fly.
+///
+/// In release builds files with identical content are embedded once. The bytes saved are reported
+/// as a cargo warning by the build scripts generating the implementation with `tower-embed-build`,
+/// the derive macro does not report them since procedural macros cannot emit notes on stable
+/// Rust.
+///
+/// The contents can be stored compressed in release builds with `#[embed(storage = "...")]`,
+/// either `"zstd"` or `"brotli"`, enabling the feature of the same name of `tower-embed`. The
+/// compressed form is sent to clients accepting the encoding, and decompressed on the fly for the
+/// others. Files that do not shrink are stored as they are. The bytes saved are reported as a cargo
+/// warning by the build scripts generating the implementation with `tower-embed-build`, like the
+/// savings of the deduplication, and not by the derive macro. Each content is compressed once, the
+/// compressed contents are cached in the `tower-embed` directory of the target directory and
+/// included from there.
+///
+/// With `#[embed(packed = true)]` the files are packed in a single archive with an index of the
+/// entries, written in the `tower-embed` directory of the target directory and embedded once,
+/// instead of expanding each file and its metadata. This reduces the compile time and the memory
+/// used by `rustc` for folders with many files.
+///
+/// In release builds the `Last-Modified` time of the files is resolved at compile time, the source
+/// is specified with `#[embed(last_modified = ...)]`: `"mtime"` for the modification time in the
+/// filesystem, the default; `"source_date_epoch"` for the time in the `SOURCE_DATE_EPOCH`
+/// environment variable; `"git"` for the time of the last commit changing each file; `"none"` to
+/// omit the header; or a fixed Unix timestamp. The files not committed fall back to the
+/// modification time, while `SOURCE_DATE_EPOCH` not set is a compile error.
+///
+/// The Subresource Integrity metadata of the files matching the glob pattern specified with
+/// `#[embed(integrity = "...")]` are computed at compile time, and resolved by the generated
+/// `integrity` method. The hash algorithm is specified with `#[embed(integrity_algorithm =
+/// "...")]`, either `"sha256"`, `"sha384"` or `"sha512"`, the default is `"sha384"`. In debug
+/// builds the files can change without recompiling, so no metadata is returned. Both attributes
+/// require the `integrity` feature of `tower-embed`.
+///
+/// The `Content-Security-Policy` of the HTML files matching the glob pattern specified with
+/// `#[embed(csp = "...")]` is computed at compile time, allowing their inline `<script>` and
+/// `<style>` elements with SHA-256 hashes, and resolved by the `content_security_policy` method of
+/// `Embed`. The policy is built from the template specified with `#[embed(csp_policy = "...")]`,
+/// where `{script-hashes}` and `{style-hashes}` are replaced with the hashes of the page, the
+/// default is `"default-src 'self'; script-src 'self' {script-hashes}; style-src 'self'
+/// {style-hashes}; object-src 'none'; base-uri 'self'"`. In debug builds no policy is returned.
+/// Both attributes require the `csp` feature of `tower-embed`.
+///
+/// The build manifest generated by a bundler can be parsed with `#[embed(manifest = "...")]`,
+/// either `"vite"`, `"webpack"` or `"trunk"`, then the built files of an entry point are resolved
+/// by the generated `entry` method, as root-relative URLs. The manifest is read from the default
+/// location of the bundler, or from `#[embed(manifest_file = "...")]`, relative to the folder, and
+/// it is not served. Trunk has no manifest, the files linked by the generated `index.html` are
+/// resolved by name without the hash, like `app.js`, and the page is served. The attribute
+/// requires the `manifest` feature of `tower-embed`.
+///
+/// The typed handles of the embedded files are created with `tower_embed::asset!`, checking at
+/// compile time that the file exists.
+///
+/// The same implementation can be generated by a build script with `tower-embed-build`, so that
+/// the folder is walked only when a file changes.
 #[proc_macro_derive(Embed, attributes(embed))]
 pub fn derive_embed(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let input = syn::parse_macro_input!(input as syn::DeriveInput);
-
-    expand_derive_embed(input)
-        .unwrap_or_else(|err| err.to_compile_error())
-        .into()
+    tower_embed_build::__derive_embed(input.into()).into()
 }
 
-fn expand_derive_embed(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
-    let DeriveEmbed { ident, attrs } = DeriveEmbed::from_ast(&input)?;
-    let DeriveEmbedAttrs {
-        folder,
-        crate_path,
-        index,
-    } = attrs;
-
-    let root = root_absolute_path(&folder);
-    let embedded_files = get_files(&root, &index).map(|file| {
-        let last_modified = tower_embed_core::last_modified(file.absolute_path.as_std_path())
-            .ok()
-            .and_then(|headers::LastModified(time)| {
-                time.duration_since(std::time::UNIX_EPOCH)
-                    .map(|duration| duration.as_secs())
-                    .ok()
-            });
-        let last_modified = match last_modified {
-            Some(secs) => quote::quote! { headers::LastModified::from_unix_timestamp(#secs) },
-            None => quote::quote! { None },
-        };
-
-        let relative_path = file.relative_path.as_str();
-        let absolute_path = file.absolute_path.as_str();
-        let redirect_path = format!("{relative_path}/{index}");
-        let redirect_path = redirect_path.trim_start_matches('/');
-
-        match file.kind {
-            FileKind::File => quote::quote! {{
-                let content = include_bytes!(#absolute_path).as_slice();
-                let metadata = Metadata {
-                    content_type: #crate_path::core::content_type(Path::new(#relative_path)),
-                    etag: Some(#crate_path::core::etag(content)),
-                    last_modified: #last_modified,
-                };
-                [(#relative_path, Entry::File(content, metadata))]
-            }},
-            FileKind::Dir => quote::quote! {{
-                [
-                    (#relative_path, Entry::Redirect(#redirect_path)),
-                    (concat!(#relative_path, "/"), Entry::Redirect(#redirect_path)),
-                ]
-            }},
-        }
-    });
-
-    let root = root.as_str();
-
-    let expanded = quote::quote! {
-        impl #crate_path::Embed for #ident {
-            #[cfg(not(debug_assertions))]
-            fn get(path: &str) -> impl Future<Output = std::io::Result<#crate_path::core::Embedded>> + Send + 'static {
-                use std::{collections::HashMap, sync::LazyLock, path::Path};
-
-                use #crate_path::core::{Content, Embedded, Metadata, headers};
-
-                enum Entry {
-                    File(&'static [u8], Metadata),
-                    Redirect(&'static str),
-                }
-
-                const FILES: LazyLock<HashMap<&'static str, Entry>> = LazyLock::new(|| {
-                    let mut m = HashMap::new();
-                    #(m.extend(#embedded_files);)*
-                    m
-                });
-
-                let mut path = path;
-                let output = loop {
-                    match FILES.get(path) {
-                        Some(Entry::File(bytes, metadata)) => break Ok(Embedded {
-                            content: Content::from_static(bytes),
-                            metadata: metadata.clone(),
-                        }),
-                        Some(Entry::Redirect(redirect_path)) => {
-                            path = redirect_path;
-                        }
-                        None => break Err(std::io::ErrorKind::NotFound.into()),
-                    };
-                };
-                std::future::ready(output)
-            }
-
-            #[cfg(debug_assertions)]
-            fn get(path: &str) -> impl Future<Output = std::io::Result<#crate_path::core::Embedded>> + Send + 'static {
-                use std::path::Path;
-
-                use #crate_path::core::{Content, Embedded, Metadata};
-
-                const ROOT: &str = #root;
-
-                let mut filename = Path::new(ROOT).join(path);
-                let stripped_path = Path::new(ROOT).join(path.trim_end_matches('/'));
-                if stripped_path.is_dir() {
-                    filename = filename.join(#index);
-                }
-
-                let metadata = Metadata {
-                    content_type: #crate_path::core::content_type(&filename),
-                    etag: None,
-                    last_modified: None,
-                };
-
-                async move {
-                    #crate_path::file::File::open(&filename).await.map(|file| {
-                        Embedded {
-                            content: Content::from_stream(file),
-                            metadata,
-                        }
-                    })
-                }
-            }
-        }
-    };
-
-    Ok(expanded)
-}
-
-/// A source data annotated with `#[derive(Embed)]``
-struct DeriveEmbed {
-    /// The struct name
-    ident: syn::Ident,
-    /// Attributes of structure
-    attrs: DeriveEmbedAttrs,
-}
-
-/// Attributes for `Embed` derive macro.
-struct DeriveEmbedAttrs {
-    /// The folder to embed
-    folder: String,
-    /// The path to the crate `tower_embed`
-    crate_path: syn::Path,
-    /// The index file name
-    index: Cow<'static, str>,
-}
-
-impl DeriveEmbed {
-    fn from_ast(input: &syn::DeriveInput) -> syn::Result<Self> {
-        let syn::Data::Struct(data) = &input.data else {
-            return Err(syn::Error::new_spanned(
-                input,
-                "`Embed` can only be derived for unit structs",
-            ));
-        };
-
-        if !matches!(&data.fields, syn::Fields::Unit) {
-            return Err(syn::Error::new_spanned(
-                &data.fields,
-                "`Embed` can only be derived for unit structs",
-            ));
-        }
-
-        let ident = input.ident.clone();
-        let attrs = DeriveEmbedAttrs::from_ast(input)?;
-
-        Ok(Self { ident, attrs })
-    }
-}
-
-impl DeriveEmbedAttrs {
-    fn from_ast(input: &syn::DeriveInput) -> syn::Result<Self> {
-        let mut folder = None;
-        let mut crate_path = None;
-        let mut index = None;
-
-        for attr in &input.attrs {
-            if !attr.path().is_ident("embed") {
-                continue;
-            }
-
-            let list = attr.meta.require_list()?;
-            if list.tokens.is_empty() {
-                continue;
-            }
-
-            list.parse_nested_meta(|meta| {
-                if meta.path.is_ident("folder") {
-                    let value: syn::LitStr = meta.value()?.parse()?;
-                    folder = Some(value.value());
-                } else if meta.path.is_ident("crate") {
-                    let value: syn::Path = meta.value()?.parse()?;
-                    crate_path = Some(value);
-                } else if meta.path.is_ident("index") {
-                    let value: syn::LitStr = meta.value()?.parse()?;
-                    index = Some(Cow::Owned(value.value()));
-                } else {
-                    let name = meta.path.to_token_stream();
-                    return Err(syn::Error::new_spanned(
-                        meta.path,
-                        format_args!("unknown `embed` attribute for `{}`", name),
-                    ));
-                }
-                Ok(())
-            })?;
-        }
-
-        let Some(folder) = folder else {
-            return Err(syn::Error::new_spanned(
-                input,
-                "#[derive(Embed)] requires `folder` attribute",
-            ));
-        };
-
-        let crate_path = crate_path.unwrap_or_else(|| syn::parse_quote! { tower_embed });
-        let index = index.unwrap_or(Cow::Borrowed("index.html"));
-
-        Ok(Self {
-            folder,
-            crate_path,
-            index,
-        })
-    }
-}
-
-fn root_absolute_path(folder: &str) -> PathBuf {
-    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")
-        .expect("missing CARGO_MANIFEST_DIR environment variable");
-
-    Path::new(&manifest_dir).join(folder)
-}
-
-fn get_files(root: &Path, index: &str) -> impl Iterator<Item = File> {
-    walkdir::WalkDir::new(root)
-        .follow_links(true)
-        .sort_by_file_name()
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter_map(move |entry| {
-            let kind = if entry.file_type().is_file() {
-                FileKind::File
-            } else if entry.file_type().is_dir() {
-                if !entry.path().join(index).is_file() {
-                    return None;
-                }
-
-                FileKind::Dir
-            } else {
-                return None;
-            };
-
-            let absolute_path: &Path = entry.path().try_into().unwrap();
-            let absolute_path = absolute_path.to_path_buf();
-
-            let relative_path = absolute_path
-                .canonicalize_utf8()
-                .unwrap()
-                .strip_prefix(root)
-                .unwrap()
-                .to_path_buf();
-
-            Some(File {
-                kind,
-                relative_path,
-                absolute_path,
-            })
-        })
-}
-
-struct File {
-    kind: FileKind,
-    relative_path: PathBuf,
-    absolute_path: PathBuf,
-}
-
-enum FileKind {
-    File,
-    Dir,
+/// Returns an anonymous value implementing the `Embed` trait, embedding assets from a folder.
+///
+/// The folder is followed by the options of the `#[embed(...)]` attributes of the derive macro,
+/// or it is omitted to embed only single files:
+///
+/// ```ignore
+/// let assets = tower_embed::embed!("assets", index = "index.htm");
+/// let favicon = tower_embed::embed!(file = "static/favicon.ico");
+/// ```
+#[proc_macro]
+pub fn embed(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    tower_embed_build::__embed(input.into()).into()
 }
@@ -1,5 +1,5 @@
 use axum::Router;
-use tower_embed::{Embed, EmbedExt, ServeEmbed};
+use tower_embed::{Embed, EmbedExt, ServeEmbed, asset};
 
 #[derive(Embed)]
 #[embed(folder = "assets")]
@@ -8,7 +8,7 @@ struct Assets;
 #[tokio::main]
 async fn main() {
     let assets = ServeEmbed::builder()
-        .not_found_service(Assets::not_found_page("404.html"))
+        .not_found_service(Assets::not_found_page(asset!(Assets, "404.html")))
         .build::<Assets>();
     let router = Router::new().fallback_service(assets);
 
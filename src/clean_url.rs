@@ -0,0 +1,66 @@
+use crate::Embed;
+
+/// Returns the location of the clean URL of a file, if `path` has an extension that can be
+/// omitted.
+///
+/// The location is relative to the requested URL, so that it is still valid when the service is
+/// nested under a prefix.
+pub(crate) fn clean_location<E: Embed>(path: &str) -> Option<String> {
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let (stem, _) = name.rsplit_once('.')?;
+    if stem.is_empty() || E::index(dir).is_some_and(|index| index == path) {
+        return None;
+    }
+
+    let clean_path = &path[..path.len() - name.len() + stem.len()];
+    E::clean_url(clean_path)
+        .is_some_and(|target| target == path)
+        .then(|| stem.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::core::Embedded;
+
+    struct Assets;
+
+    impl Embed for Assets {
+        fn get(_path: &str) -> impl Future<Output = std::io::Result<Embedded>> + Send + 'static {
+            std::future::ready(Err(std::io::ErrorKind::NotFound.into()))
+        }
+
+        fn index(path: &str) -> Option<Cow<'static, str>> {
+            match path.trim_matches('/') {
+                "docs" => Some(Cow::Borrowed("docs/index.html")),
+                _ => None,
+            }
+        }
+
+        fn clean_url(path: &str) -> Option<Cow<'static, str>> {
+            match path {
+                "about" => Some(Cow::Borrowed("about.html")),
+                "docs/intro" => Some(Cow::Borrowed("docs/intro.html")),
+                "docs/index" => Some(Cow::Borrowed("docs/index.html")),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn redirect_to_clean_url() {
+        let location = clean_location::<Assets>;
+        assert_eq!(location("about.html").as_deref(), Some("about"));
+        assert_eq!(location("docs/intro.html").as_deref(), Some("intro"));
+        assert_eq!(location("about.htm"), None);
+        assert_eq!(location("style.css"), None);
+        assert_eq!(location("about"), None);
+    }
+
+    #[test]
+    fn skip_index_files() {
+        assert_eq!(clean_location::<Assets>("docs/index.html"), None);
+    }
+}
@@ -0,0 +1,310 @@
+//! Cross-Origin Resource Sharing of the embedded assets, see
+//! <https://fetch.spec.whatwg.org/#http-cors-protocol>.
+
+use std::{sync::Arc, time::Duration};
+
+use http::{HeaderValue, Method, header};
+
+use crate::{Body, core::glob::Glob};
+
+/// The configuration of Cross-Origin Resource Sharing.
+///
+/// The responses to the requests of the allowed origins have the `Access-Control-Allow-Origin`
+/// header, and the preflight `OPTIONS` requests are answered for the `GET` and `HEAD` methods.
+///
+/// # Example
+///
+/// ```
+/// # use tower_embed::cors::Cors;
+/// let cors = Cors::origins(["https://example.com"])
+///     .paths("fonts/**")
+///     .max_age(std::time::Duration::from_secs(3600));
+/// ```
+#[derive(Clone)]
+pub struct Cors {
+    origins: AllowOrigin,
+    paths: Vec<Glob>,
+    allow_headers: Vec<HeaderValue>,
+    max_age: Option<Duration>,
+}
+
+#[derive(Clone)]
+enum AllowOrigin {
+    Any,
+    List(Vec<HeaderValue>),
+    Predicate(Arc<dyn Fn(&HeaderValue) -> bool + Send + Sync>),
+}
+
+impl Cors {
+    fn new(origins: AllowOrigin) -> Self {
+        Self {
+            origins,
+            paths: Vec::new(),
+            allow_headers: Vec::new(),
+            max_age: None,
+        }
+    }
+
+    /// Allow any origin, the responses are shared with `Access-Control-Allow-Origin: *`.
+    pub fn any() -> Self {
+        Self::new(AllowOrigin::Any)
+    }
+
+    /// Allow the origins of the list, like `https://example.com`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an origin is not a valid header value.
+    pub fn origins<I>(origins: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let origins = origins
+            .into_iter()
+            .map(|origin| {
+                let origin = origin.as_ref();
+                HeaderValue::from_str(origin)
+                    .unwrap_or_else(|_| panic!("invalid CORS origin `{origin}`"))
+            })
+            .collect();
+        Self::new(AllowOrigin::List(origins))
+    }
+
+    /// Allow the origins accepted by the predicate, called with the `Origin` header of requests.
+    pub fn origin_predicate<F>(predicate: F) -> Self
+    where
+        F: Fn(&HeaderValue) -> bool + Send + Sync + 'static,
+    {
+        Self::new(AllowOrigin::Predicate(Arc::new(predicate)))
+    }
+
+    /// Share only the assets at the paths matching the glob pattern, like `fonts/*.woff2`.
+    ///
+    /// The patterns of multiple calls are combined, by default all the assets are shared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid.
+    pub fn paths(mut self, pattern: &str) -> Self {
+        let glob = Glob::parse(pattern)
+            .unwrap_or_else(|err| panic!("invalid CORS path pattern `{pattern}`: {err}"));
+        self.paths.push(glob);
+        self
+    }
+
+    /// Allow the request headers in preflight requests, in addition to the CORS-safelisted ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a header name is invalid.
+    pub fn allow_headers<I>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let headers = headers.into_iter().map(|name| {
+            let name = name.as_ref();
+            http::HeaderName::from_bytes(name.as_bytes())
+                .map(HeaderValue::from)
+                .unwrap_or_else(|_| panic!("invalid CORS header name `{name}`"))
+        });
+        self.allow_headers.extend(headers);
+        self
+    }
+
+    /// Set how long the result of preflight requests can be cached by clients.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Returns true if the asset at the path is shared.
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        self.paths.is_empty() || self.paths.iter().any(|glob| glob.matches(path))
+    }
+
+    /// Returns the value of `Access-Control-Allow-Origin` for the `Origin` of a request, if it is
+    /// allowed.
+    fn allow_origin(&self, origin: Option<&HeaderValue>) -> Option<HeaderValue> {
+        match &self.origins {
+            AllowOrigin::Any => Some(HeaderValue::from_static("*")),
+            AllowOrigin::List(origins) => origin.filter(|origin| origins.contains(origin)).cloned(),
+            AllowOrigin::Predicate(predicate) => origin.filter(|origin| predicate(origin)).cloned(),
+        }
+    }
+
+    /// Adds the CORS headers to the response of a `GET` or `HEAD` request with the `Origin`.
+    pub(crate) fn apply(&self, origin: Option<&HeaderValue>, headers: &mut http::HeaderMap) {
+        // the response depends on the origin, unless it is shared with any origin
+        if !matches!(self.origins, AllowOrigin::Any) {
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+        if let Some(allow_origin) = self.allow_origin(origin) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        }
+    }
+
+    /// Returns the response of an `OPTIONS` request, a preflight request if it has the `Origin`
+    /// and `Access-Control-Request-Method` headers.
+    pub(crate) fn preflight(&self, request: &http::Request<()>) -> http::Response<Body> {
+        let mut response = http::Response::builder()
+            .status(http::StatusCode::NO_CONTENT)
+            .header(header::ALLOW, "GET, HEAD, OPTIONS")
+            .body(Body::empty())
+            .unwrap();
+
+        let headers = request.headers();
+        let Some(method) = headers.get(header::ACCESS_CONTROL_REQUEST_METHOD) else {
+            return response;
+        };
+        let vary = HeaderValue::from_static(
+            "origin, access-control-request-method, access-control-request-headers",
+        );
+        response.headers_mut().insert(header::VARY, vary);
+
+        let method_allowed = Method::from_bytes(method.as_bytes())
+            .is_ok_and(|method| method == Method::GET || method == Method::HEAD);
+        let headers_allowed = headers
+            .get_all(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .all(|name| {
+                self.allow_headers
+                    .iter()
+                    .any(|allowed| allowed.as_bytes().eq_ignore_ascii_case(name.as_bytes()))
+            });
+        let Some(allow_origin) = self
+            .allow_origin(headers.get(header::ORIGIN))
+            .filter(|_| method_allowed && headers_allowed)
+        else {
+            tracing::trace!(
+                "Rejected CORS preflight request for '{}'",
+                request.uri().path()
+            );
+            return response;
+        };
+
+        let headers = response.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_static("GET, HEAD"),
+        );
+        if !self.allow_headers.is_empty() {
+            let allow_headers = self
+                .allow_headers
+                .iter()
+                .filter_map(|name| name.to_str().ok())
+                .collect::<Vec<_>>()
+                .join(", ");
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                HeaderValue::from_str(&allow_headers).unwrap(),
+            );
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, headers: &[(&str, &str)]) -> http::Request<()> {
+        let mut request = http::Request::builder()
+            .method(method)
+            .uri("/fonts/a.woff2");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap()
+    }
+
+    #[test]
+    fn allowed_origins() {
+        let cors = Cors::origins(["https://example.com"]);
+
+        let mut headers = http::HeaderMap::new();
+        cors.apply(
+            Some(&HeaderValue::from_static("https://example.com")),
+            &mut headers,
+        );
+        assert_eq!(
+            headers["access-control-allow-origin"],
+            "https://example.com"
+        );
+        assert_eq!(headers["vary"], "origin");
+
+        let mut headers = http::HeaderMap::new();
+        cors.apply(
+            Some(&HeaderValue::from_static("https://other.com")),
+            &mut headers,
+        );
+        assert!(!headers.contains_key("access-control-allow-origin"));
+        assert_eq!(headers["vary"], "origin");
+
+        let mut headers = http::HeaderMap::new();
+        let cors = Cors::origin_predicate(|origin| origin.as_bytes().ends_with(b".example.com"));
+        cors.apply(
+            Some(&HeaderValue::from_static("https://www.example.com")),
+            &mut headers,
+        );
+        assert_eq!(
+            headers["access-control-allow-origin"],
+            "https://www.example.com"
+        );
+    }
+
+    #[test]
+    fn preflight_requests() {
+        let cors = Cors::any()
+            .allow_headers(["Range"])
+            .max_age(Duration::from_secs(600));
+
+        let preflight = request(
+            Method::OPTIONS,
+            &[
+                ("origin", "https://example.com"),
+                ("access-control-request-method", "GET"),
+                ("access-control-request-headers", "range"),
+            ],
+        );
+        let response = cors.preflight(&preflight);
+        assert_eq!(response.status(), http::StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(headers["access-control-allow-origin"], "*");
+        assert_eq!(headers["access-control-allow-methods"], "GET, HEAD");
+        assert_eq!(headers["access-control-allow-headers"], "range");
+        assert_eq!(headers["access-control-max-age"], "600");
+
+        let preflight = request(
+            Method::OPTIONS,
+            &[
+                ("origin", "https://example.com"),
+                ("access-control-request-method", "POST"),
+            ],
+        );
+        let response = cors.preflight(&preflight);
+        assert!(
+            !response
+                .headers()
+                .contains_key("access-control-allow-origin")
+        );
+    }
+
+    #[test]
+    fn scoped_paths() {
+        let cors = Cors::any().paths("fonts/*").paths("**/*.json");
+        assert!(cors.matches("/fonts/a.woff2"));
+        assert!(cors.matches("/data/config.json"));
+        assert!(!cors.matches("/index.html"));
+        assert!(Cors::any().matches("/index.html"));
+    }
+}
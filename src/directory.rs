@@ -0,0 +1,125 @@
+use crate::Embed;
+
+/// Policy for the trailing slash of directory URLs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Redirect `/dir` to `/dir/` with `308 Permanent Redirect`, relative links in the index file
+    /// are resolved against the directory.
+    #[default]
+    Redirect,
+    /// Redirect `/dir/` to `/dir` with `308 Permanent Redirect`.
+    Strip,
+    /// Serve the index file at both `/dir` and `/dir/`.
+    Rewrite,
+}
+
+/// Returns the location of the canonical URL of a directory, if `path` is not canonical.
+///
+/// The location is relative to the requested URL, so that it is still valid when the service is
+/// nested under a prefix.
+pub(crate) fn canonical_location<E: Embed>(
+    path: &str,
+    trailing_slash: TrailingSlash,
+    redirect_index: bool,
+) -> Option<String> {
+    if redirect_index {
+        let (dir, _) = path.rsplit_once('/').unwrap_or(("", path));
+        if E::index(dir).is_some_and(|index| index == path) {
+            return Some(match trailing_slash {
+                TrailingSlash::Strip if !dir.is_empty() => format!("../{}", last_segment(dir)),
+                _ => "./".to_string(),
+            });
+        }
+    }
+
+    let dir = path.trim_end_matches('/');
+    if dir.is_empty() {
+        return None;
+    }
+
+    match trailing_slash {
+        TrailingSlash::Redirect if !path.ends_with('/') && E::index(dir).is_some() => {
+            Some(format!("{}/", last_segment(dir)))
+        }
+        TrailingSlash::Strip if path.ends_with('/') && E::index(dir).is_some() => {
+            Some(format!("../{}", last_segment(dir)))
+        }
+        _ => None,
+    }
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit_once('/').map_or(path, |(_, segment)| segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::core::Embedded;
+
+    struct Assets;
+
+    impl Embed for Assets {
+        fn get(_path: &str) -> impl Future<Output = std::io::Result<Embedded>> + Send + 'static {
+            std::future::ready(Err(std::io::ErrorKind::NotFound.into()))
+        }
+
+        fn index(path: &str) -> Option<Cow<'static, str>> {
+            match path.trim_matches('/') {
+                "" => Some(Cow::Borrowed("index.html")),
+                "docs" => Some(Cow::Borrowed("docs/index.html")),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn redirect_trailing_slash() {
+        let location = |path| canonical_location::<Assets>(path, TrailingSlash::Redirect, false);
+        assert_eq!(location("docs").as_deref(), Some("docs/"));
+        assert_eq!(location("docs/"), None);
+        assert_eq!(location("docs/index.html"), None);
+        assert_eq!(location(""), None);
+        assert_eq!(location("about"), None);
+    }
+
+    #[test]
+    fn strip_trailing_slash() {
+        let location = |path| canonical_location::<Assets>(path, TrailingSlash::Strip, false);
+        assert_eq!(location("docs"), None);
+        assert_eq!(location("docs/").as_deref(), Some("../docs"));
+        assert_eq!(location(""), None);
+    }
+
+    #[test]
+    fn rewrite_trailing_slash() {
+        let location = |path| canonical_location::<Assets>(path, TrailingSlash::Rewrite, false);
+        assert_eq!(location("docs"), None);
+        assert_eq!(location("docs/"), None);
+    }
+
+    #[test]
+    fn redirect_index() {
+        let location =
+            |path, trailing_slash| canonical_location::<Assets>(path, trailing_slash, true);
+        assert_eq!(
+            location("index.html", TrailingSlash::Redirect).as_deref(),
+            Some("./")
+        );
+        assert_eq!(
+            location("docs/index.html", TrailingSlash::Redirect).as_deref(),
+            Some("./")
+        );
+        assert_eq!(
+            location("docs/index.html", TrailingSlash::Strip).as_deref(),
+            Some("../docs")
+        );
+        assert_eq!(
+            location("index.html", TrailingSlash::Strip).as_deref(),
+            Some("./")
+        );
+        assert_eq!(location("docs/other.html", TrailingSlash::Redirect), None);
+    }
+}
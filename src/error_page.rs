@@ -0,0 +1,201 @@
+use std::{collections::HashMap, convert::Infallible};
+
+use http::StatusCode;
+
+use crate::{
+    Body,
+    core::headers::{self, HeaderMapExt},
+};
+
+/// Service used to render the page of an error response.
+type ErrorPageService =
+    tower::util::BoxCloneSyncService<http::Request<()>, http::Response<Body>, Infallible>;
+
+/// Custom error pages, indexed by status code.
+#[derive(Default)]
+pub(crate) struct ErrorPages(HashMap<StatusCode, ErrorPageService>);
+
+impl ErrorPages {
+    pub(crate) fn insert(&mut self, status: StatusCode, service: ErrorPageService) {
+        self.0.insert(status, service);
+    }
+
+    /// Builds the error response with the given status code.
+    ///
+    /// Clients preferring JSON receive an `application/problem+json` body as defined by RFC 9457,
+    /// otherwise the custom error page is rendered if any.
+    pub(crate) async fn respond(
+        &self,
+        status: StatusCode,
+        request: http::Request<()>,
+    ) -> http::Response<Body> {
+        use tower::ServiceExt;
+
+        let mut response = if prefers_problem_details(&request) {
+            problem_details_response(status, request.uri().path())
+        } else if let Some(service) = self.0.get(&status).cloned() {
+            let mut request = request;
+            request.headers_mut().remove(http::header::IF_NONE_MATCH);
+            request
+                .headers_mut()
+                .remove(http::header::IF_MODIFIED_SINCE);
+
+            let service = service.ready_oneshot().await.unwrap();
+            let mut response = service.oneshot(request).await.unwrap();
+            response.headers_mut().remove(http::header::ETAG);
+            response.headers_mut().remove(http::header::LAST_MODIFIED);
+            response
+        } else {
+            http::Response::new(Body::empty())
+        };
+
+        *response.status_mut() = status;
+        response.headers_mut().insert(
+            http::header::CACHE_CONTROL,
+            http::HeaderValue::from_static("no-store"),
+        );
+        if status == StatusCode::METHOD_NOT_ALLOWED {
+            response.headers_mut().insert(
+                http::header::ALLOW,
+                http::HeaderValue::from_static("GET, HEAD"),
+            );
+        }
+        response
+    }
+}
+
+/// Returns true if the client prefers a JSON representation over an HTML one.
+fn prefers_problem_details(request: &http::Request<()>) -> bool {
+    static AVAILABLE: std::sync::LazyLock<[mime::Mime; 3]> = std::sync::LazyLock::new(|| {
+        [
+            mime::TEXT_HTML,
+            "application/problem+json".parse().unwrap(),
+            mime::APPLICATION_JSON,
+        ]
+    });
+
+    let Some(accept) = request.headers().typed_get::<headers::Accept>() else {
+        return false;
+    };
+    accept
+        .negotiate(AVAILABLE.as_slice())
+        .is_some_and(|mime| mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON))
+}
+
+fn problem_details_response(status: StatusCode, instance: &str) -> http::Response<Body> {
+    let title = status.canonical_reason().unwrap_or("Unknown Error");
+    let body = format!(
+        r#"{{"type":"about:blank","title":"{}","status":{},"instance":"{}"}}"#,
+        json_escape(title),
+        status.as_u16(),
+        json_escape(instance),
+    );
+
+    http::Response::builder()
+        .header(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("application/problem+json"),
+        )
+        .body(Body::full(body.into()))
+        .unwrap()
+}
+
+fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_pages() -> ErrorPages {
+        let service = tower::service_fn(|_request: http::Request<()>| async {
+            let response = http::Response::builder()
+                .header(http::header::CONTENT_TYPE, "text/html")
+                .header(http::header::ETAG, "\"page\"")
+                .body(Body::full("<h1>Not Found</h1>".into()))
+                .unwrap();
+            Ok::<_, Infallible>(response)
+        });
+        let mut pages = ErrorPages::default();
+        pages.insert(
+            StatusCode::NOT_FOUND,
+            tower::util::BoxCloneSyncService::new(service),
+        );
+        pages
+    }
+
+    fn request(accept: Option<&str>) -> http::Request<()> {
+        let mut request = http::Request::builder().uri("/missing\"page");
+        if let Some(accept) = accept {
+            request = request.header(http::header::ACCEPT, accept);
+        }
+        request.body(()).unwrap()
+    }
+
+    async fn body(response: http::Response<Body>) -> String {
+        let content = crate::html::collect(response.into_body()).await.unwrap();
+        String::from_utf8(content.into()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn custom_error_page() {
+        let pages = error_pages();
+        let accept = Some("text/html,application/xhtml+xml,*/*;q=0.8");
+        let response = pages.respond(StatusCode::NOT_FOUND, request(accept)).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()["cache-control"], "no-store");
+        assert_eq!(response.headers()["content-type"], "text/html");
+        assert!(!response.headers().contains_key(http::header::ETAG));
+        assert_eq!(body(response).await, "<h1>Not Found</h1>");
+
+        let response = pages.respond(StatusCode::NOT_FOUND, request(None)).await;
+        assert_eq!(body(response).await, "<h1>Not Found</h1>");
+    }
+
+    #[tokio::test]
+    async fn problem_details() {
+        let pages = error_pages();
+        for accept in [
+            "application/json",
+            "application/problem+json, text/html;q=0.5",
+        ] {
+            let response = pages
+                .respond(StatusCode::NOT_FOUND, request(Some(accept)))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert_eq!(
+                response.headers()["content-type"],
+                "application/problem+json"
+            );
+            assert_eq!(
+                body(response).await,
+                r#"{"type":"about:blank","title":"Not Found","status":404,"instance":"/missing\"page"}"#
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn default_error_page() {
+        let pages = ErrorPages::default();
+        let response = pages
+            .respond(StatusCode::METHOD_NOT_ALLOWED, request(Some("text/html")))
+            .await;
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["allow"], "GET, HEAD");
+        assert_eq!(response.headers()["cache-control"], "no-store");
+        assert_eq!(body(response).await, "");
+    }
+}
@@ -20,6 +20,17 @@ impl File {
     }
 }
 
+/// Reads a text file, replacing the placeholders with the values of the variables.
+pub async fn substitute(path: &std::path::Path, vars: &[(&str, &str)]) -> std::io::Result<Bytes> {
+    let content = tokio::fs::read_to_string(path).await?;
+    crate::core::substitute::substitute(&content, vars)
+        .map(Bytes::from)
+        .map_err(|err| {
+            let message = format!("{}: {}", path.display(), err);
+            std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+        })
+}
+
 impl Stream for File {
     type Item = Result<Bytes, BoxError>;
 
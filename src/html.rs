@@ -0,0 +1,588 @@
+//! Transformations of the HTML pages at runtime.
+//!
+//! The transformations are applied in order to the decoded content of the `text/html` embedded
+//! files, see [`ServeEmbedBuilder::html_transform`](crate::ServeEmbedBuilder::html_transform).
+//! The `Content-Length` and `ETag` headers of the response are computed from the transformed page,
+//! the entity tag with the hash algorithm of the assets, and the `Last-Modified` header is
+//! omitted.
+
+use std::{fmt::Write, pin::Pin, sync::Arc};
+
+use bytes::Bytes;
+
+use crate::{
+    Body,
+    core::{
+        BoxError,
+        hasher::Strength,
+        headers::{self, HeaderMapExt},
+    },
+};
+
+/// The HTML page of a response, with its headers.
+pub struct HtmlPage {
+    /// The decoded content of the page.
+    pub html: String,
+    /// The headers of the response.
+    pub headers: http::HeaderMap,
+}
+
+/// A transformation of the HTML pages.
+pub trait HtmlTransform: Send + Sync + 'static {
+    /// Transforms the page served for the request.
+    fn transform(
+        &self,
+        request: &http::Request<()>,
+        page: HtmlPage,
+    ) -> impl Future<Output = HtmlPage> + Send;
+}
+
+/// Transformation with a boxed future, to be stored in the configuration.
+pub(crate) trait DynHtmlTransform: Send + Sync + 'static {
+    fn transform<'a>(
+        &'a self,
+        request: &'a http::Request<()>,
+        page: HtmlPage,
+    ) -> Pin<Box<dyn Future<Output = HtmlPage> + Send + 'a>>;
+}
+
+impl<T: HtmlTransform> DynHtmlTransform for T {
+    fn transform<'a>(
+        &'a self,
+        request: &'a http::Request<()>,
+        page: HtmlPage,
+    ) -> Pin<Box<dyn Future<Output = HtmlPage> + Send + 'a>> {
+        Box::pin(HtmlTransform::transform(self, request, page))
+    }
+}
+
+pub(crate) type BoxHtmlTransform = Arc<dyn DynHtmlTransform>;
+
+/// Marks a response whose page is transformed, with the strength of its entity tag if any.
+#[derive(Clone, Copy)]
+pub(crate) struct Transformable(pub(crate) Option<Strength>);
+
+/// Returns true if the pages of the content type are transformed.
+pub(crate) fn is_html(content_type: &headers::ContentType) -> bool {
+    content_type.0.essence_str() == mime::TEXT_HTML.essence_str()
+}
+
+/// Returns a copy of the request passed to the transformations, without extensions.
+pub(crate) fn request_copy(request: &http::Request<()>) -> http::Request<()> {
+    let mut copy = http::Request::new(());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    copy
+}
+
+/// Applies the transformations to the page of a response marked as [`Transformable`], the entity
+/// tag of the transformed page is computed by `etag`.
+pub(crate) async fn apply(
+    transforms: &[BoxHtmlTransform],
+    request: &http::Request<()>,
+    response: http::Response<Body>,
+    etag: fn(&[u8], Strength) -> headers::ETag,
+) -> Result<http::Response<Body>, BoxError> {
+    let (mut parts, body) = response.into_parts();
+    let Some(Transformable(strength)) = parts.extensions.remove() else {
+        return Ok(http::Response::from_parts(parts, body));
+    };
+
+    let content = collect(body).await?;
+    let html = match String::from_utf8(content.into()) {
+        Ok(html) => html,
+        Err(err) => {
+            tracing::warn!(
+                "Not transforming HTML page '{}': {err}",
+                request.uri().path()
+            );
+            let body = Body::full(Bytes::from(err.into_bytes()));
+            return Ok(http::Response::from_parts(parts, body));
+        }
+    };
+
+    let mut page = HtmlPage {
+        html,
+        headers: std::mem::take(&mut parts.headers),
+    };
+    for transform in transforms {
+        page = transform.transform(request, page).await;
+    }
+
+    parts.headers = page.headers;
+    let content = Bytes::from(page.html);
+    parts.headers.remove(http::header::LAST_MODIFIED);
+    parts
+        .headers
+        .insert(http::header::CONTENT_LENGTH, content.len().into());
+
+    if let Some(strength) = strength
+        && parts.status == http::StatusCode::OK
+    {
+        let etag = etag(&content, strength);
+        let if_none_match = request.headers().typed_get::<headers::IfNoneMatch>();
+        parts.headers.typed_insert(etag.clone());
+        // the headers of the response are kept, like `Vary` and `Cache-Control`
+        if let Some(if_none_match) = if_none_match
+            && !if_none_match.condition_passes(&etag)
+        {
+            parts.status = http::StatusCode::NOT_MODIFIED;
+            parts.headers.remove(http::header::CONTENT_LENGTH);
+            return Ok(http::Response::from_parts(parts, Body::empty()));
+        }
+    }
+
+    Ok(http::Response::from_parts(parts, Body::full(content)))
+}
+
+pub(crate) async fn collect(body: Body) -> Result<Bytes, BoxError> {
+    use http_body::Body as _;
+
+    let mut body = std::pin::pin!(body);
+    let mut content = Vec::new();
+    while let Some(frame) = std::future::poll_fn(|cx| body.as_mut().poll_frame(cx)).await {
+        if let Ok(data) = frame?.into_data() {
+            content.extend_from_slice(&data);
+        }
+    }
+    Ok(Bytes::from(content))
+}
+
+/// Injects a nonce in the `<script>` and `<style>` tags of each page, and sets the
+/// `Content-Security-Policy` header allowing it.
+///
+/// A new nonce is generated for each response, so the pages are not stored by caches. It should be
+/// the last transformation, to include the tags inserted by the others.
+#[derive(Clone, Debug)]
+pub struct CspNonce {
+    policy: String,
+}
+
+impl Default for CspNonce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CspNonce {
+    /// The default policy, a strict policy allowing only the scripts with the nonce and the ones
+    /// they load.
+    pub const DEFAULT_POLICY: &str =
+        "script-src 'nonce-{nonce}' 'strict-dynamic'; object-src 'none'; base-uri 'none'";
+
+    /// Create a new [`CspNonce`] with the [default policy](Self::DEFAULT_POLICY).
+    pub fn new() -> Self {
+        Self {
+            policy: Self::DEFAULT_POLICY.to_string(),
+        }
+    }
+
+    /// Set the `Content-Security-Policy` header, where `{nonce}` is replaced with the nonce.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the policy is not a valid header value.
+    pub fn policy(mut self, policy: impl Into<String>) -> Self {
+        let policy = policy.into();
+        assert!(
+            http::HeaderValue::from_str(&policy).is_ok(),
+            "invalid Content-Security-Policy `{policy}`"
+        );
+        self.policy = policy;
+        self
+    }
+}
+
+impl HtmlTransform for CspNonce {
+    async fn transform(&self, _request: &http::Request<()>, mut page: HtmlPage) -> HtmlPage {
+        let nonce = nonce();
+        page.html = inject_nonce(&page.html, &nonce);
+
+        let policy = self.policy.replace("{nonce}", &nonce);
+        let policy = http::HeaderValue::from_str(&policy).expect("valid policy");
+        page.headers
+            .insert(http::header::CONTENT_SECURITY_POLICY, policy);
+        page.headers.insert(
+            http::header::CACHE_CONTROL,
+            http::HeaderValue::from_static("no-store"),
+        );
+        page
+    }
+}
+
+/// Returns a random nonce of 128 bits, hex encoded.
+fn nonce() -> String {
+    let mut bytes = [0; 16];
+    getrandom::fill(&mut bytes).expect("failed to generate a random nonce");
+    bytes
+        .iter()
+        .fold(String::with_capacity(32), |mut nonce, byte| {
+            let _ = write!(nonce, "{byte:02x}");
+            nonce
+        })
+}
+
+/// Adds the `nonce` attribute to the `<script>` and `<style>` tags.
+fn inject_nonce(html: &str, nonce: &str) -> String {
+    // only ASCII characters are lowercased, the offsets are the same of the original text
+    let lowercase = html.to_ascii_lowercase();
+    let attribute = format!(" nonce=\"{nonce}\"");
+
+    let mut output = String::with_capacity(html.len());
+    let mut position = 0;
+    let tags =
+        tags(&lowercase).filter(|tag| !tag.closing && matches!(tag.name, "script" | "style"));
+    for tag in tags {
+        output.push_str(&html[position..tag.name_end]);
+        output.push_str(&attribute);
+        position = tag.name_end;
+    }
+    output.push_str(&html[position..]);
+    output
+}
+
+/// The elements whose content is text, where `<` does not start a tag.
+const TEXT_ELEMENTS: &[&str] = &[
+    "script", "style", "textarea", "title", "xmp", "iframe", "noembed", "noframes",
+];
+
+/// A start or end tag of a page.
+struct Tag<'a> {
+    /// The name of the tag, in lowercase.
+    name: &'a str,
+    /// True for an end tag.
+    closing: bool,
+    /// The offset after the name of the tag.
+    name_end: usize,
+}
+
+/// Returns the tags of the lowercase page, skipping the comments, the values of the attributes and
+/// the content of the text elements, like the strings of the scripts.
+fn tags(lowercase: &str) -> impl Iterator<Item = Tag<'_>> {
+    let mut position = 0;
+    std::iter::from_fn(move || {
+        loop {
+            let start = position + lowercase[position..].find('<')?;
+            let rest = &lowercase[start..];
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                position = comment
+                    .find("-->")
+                    .map_or(lowercase.len(), |end| start + 4 + end + 3);
+                continue;
+            }
+
+            let closing = rest.starts_with("</");
+            let name_start = start + if closing { 2 } else { 1 };
+            if !lowercase[name_start..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+                // doctypes and bogus comments end at the first `>`, any other `<` is text
+                position = if closing || rest.starts_with("<!") || rest.starts_with("<?") {
+                    rest.find('>')
+                        .map_or(lowercase.len(), |end| start + end + 1)
+                } else {
+                    start + 1
+                };
+                continue;
+            }
+
+            let name_end = lowercase[name_start..]
+                .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+                .map_or(lowercase.len(), |end| name_start + end);
+            let name = &lowercase[name_start..name_end];
+            position = tag_end(lowercase, name_end);
+            if !closing && TEXT_ELEMENTS.contains(&name) {
+                position = end_tag_start(lowercase, position, name);
+            }
+            return Some(Tag {
+                name,
+                closing,
+                name_end,
+            });
+        }
+    })
+}
+
+/// Returns the offset after the `>` closing a tag, skipping the quoted values of the attributes.
+fn tag_end(lowercase: &str, position: usize) -> usize {
+    let bytes = lowercase.as_bytes();
+    let mut index = position;
+    let mut value = false;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'>' => return index + 1,
+            quote @ (b'"' | b'\'') if value => {
+                index = lowercase[index + 1..]
+                    .find(quote as char)
+                    .map_or(bytes.len(), |end| index + 1 + end);
+                value = false;
+            }
+            b'=' => value = true,
+            byte if byte.is_ascii_whitespace() => {}
+            _ => value = false,
+        }
+        index += 1;
+    }
+    bytes.len()
+}
+
+/// Returns the offset of the end tag of a text element, from the end of its start tag.
+fn end_tag_start(lowercase: &str, position: usize, name: &str) -> usize {
+    let end_tag = format!("</{name}");
+    lowercase[position..]
+        .match_indices(&end_tag)
+        .map(|(start, _)| position + start)
+        .find(|start| {
+            lowercase[start + end_tag.len()..]
+                .starts_with(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        })
+        .unwrap_or(lowercase.len())
+}
+
+/// Injects a configuration known at runtime in each page, as a JSON value assigned to a global
+/// variable of a `<script>` tag inserted at the end of `<head>`.
+///
+/// Without the closing `</head>` tag, the script is inserted after the opening `<head>` tag, or
+/// before the `<body>` tag, or after the doctype.
+///
+/// The `Content-Security-Policy` headers of the page, like the one computed at compile time or the
+/// one of [`Preset::Strict`](crate::security::Preset::Strict), are extended to allow the script
+/// with its hash. Combined with [`CspNonce`], the nonce is added to the script instead.
+#[derive(Clone, Debug)]
+pub struct InjectConfig {
+    variable: String,
+    json: String,
+}
+
+impl InjectConfig {
+    /// Create a new [`InjectConfig`] assigning the JSON value to `window.__CONFIG__`.
+    ///
+    /// The value is inserted as it is, it should be serialized with a JSON library.
+    pub fn new(json: impl Into<String>) -> Self {
+        Self {
+            variable: "__CONFIG__".to_string(),
+            json: json.into(),
+        }
+    }
+
+    /// Set the name of the global variable, the default is `__CONFIG__`.
+    pub fn variable(mut self, variable: impl Into<String>) -> Self {
+        self.variable = variable.into();
+        self
+    }
+
+    fn script_content(&self) -> String {
+        // the value cannot close the script
+        let json = self.json.replace("</", "<\\/").replace("<!--", "<\\!--");
+        format!("window[{:?}] = {};", self.variable, json)
+    }
+}
+
+impl HtmlTransform for InjectConfig {
+    async fn transform(&self, _request: &http::Request<()>, mut page: HtmlPage) -> HtmlPage {
+        let content = self.script_content();
+        let position = script_position(&page.html);
+        page.html
+            .insert_str(position, &format!("<script>{content}</script>"));
+
+        let hash_source = hash_source(&content);
+        for name in [
+            http::header::CONTENT_SECURITY_POLICY,
+            http::header::CONTENT_SECURITY_POLICY_REPORT_ONLY,
+        ] {
+            let policies = page
+                .headers
+                .get_all(&name)
+                .iter()
+                .map(|policy| match policy.to_str() {
+                    Ok(value) => allow_script(value, &hash_source)
+                        .and_then(|value| http::HeaderValue::from_str(&value).ok())
+                        .unwrap_or_else(|| policy.clone()),
+                    Err(_) => policy.clone(),
+                })
+                .collect::<Vec<_>>();
+            page.headers.remove(&name);
+            for policy in policies {
+                page.headers.append(&name, policy);
+            }
+        }
+        page
+    }
+}
+
+/// Returns the hash source of the inline script, like `'sha256-<base64 digest>'`.
+fn hash_source(content: &str) -> String {
+    use base64::Engine;
+    use sha2::Digest;
+
+    let digest = sha2::Sha256::digest(content.as_bytes());
+    let digest = base64::engine::general_purpose::STANDARD.encode(digest);
+    format!("'sha256-{digest}'")
+}
+
+/// Returns the policy allowing the inline script with the hash source, `None` if the policy
+/// already allows it.
+///
+/// The hash is added to the directive of the scripts, `script-src-elem`, `script-src` or
+/// `default-src`, the last one is copied into a new `script-src` directive.
+fn allow_script(policy: &str, hash_source: &str) -> Option<String> {
+    let mut directives = policy
+        .split(';')
+        .map(|directive| directive.split_whitespace().collect::<Vec<_>>())
+        .filter(|directive| !directive.is_empty())
+        .collect::<Vec<_>>();
+    let find = |name: &str| {
+        directives
+            .iter()
+            .position(|directive| directive[0].eq_ignore_ascii_case(name))
+    };
+    let index = match find("script-src-elem").or_else(|| find("script-src")) {
+        Some(index) => index,
+        None => {
+            let mut directive = directives[find("default-src")?].clone();
+            directive[0] = "script-src";
+            directives.push(directive);
+            directives.len() - 1
+        }
+    };
+
+    let directive = &mut directives[index];
+    let is_source = |prefix: &str| {
+        directive[1..]
+            .iter()
+            .any(|source| source.to_ascii_lowercase().starts_with(prefix))
+    };
+    // hashes and nonces disable 'unsafe-inline', allowing all the inline scripts
+    if is_source("'unsafe-inline'") && !is_source("'sha") && !is_source("'nonce-") {
+        return None;
+    }
+    directive.retain(|source| !source.eq_ignore_ascii_case("'none'"));
+    directive.push(hash_source);
+
+    let directives = directives
+        .iter()
+        .map(|directive| directive.join(" "))
+        .collect::<Vec<_>>();
+    Some(directives.join("; "))
+}
+
+/// Returns the offset where a script is inserted in the head of the page.
+fn script_position(html: &str) -> usize {
+    // only ASCII characters are lowercased, the offsets are the same of the original text
+    let lowercase = html.to_ascii_lowercase();
+    let tag_start = |name: &str| {
+        lowercase
+            .match_indices(name)
+            .map(|(start, _)| start)
+            .find(|start| {
+                lowercase[start + name.len()..]
+                    .starts_with(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+            })
+    };
+    let tag_end = |start: usize| lowercase[start..].find('>').map(|end| start + end + 1);
+
+    tag_start("</head")
+        .or_else(|| tag_start("<head").and_then(tag_end))
+        .or_else(|| tag_start("<body"))
+        .or_else(|| tag_start("<!doctype").and_then(tag_end))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonce_in_tags() {
+        let html = "<head><SCRIPT src=a.js></SCRIPT><style>p{}</style><scripts><script/></head>";
+        assert_eq!(
+            inject_nonce(html, "abc"),
+            "<head><SCRIPT nonce=\"abc\" src=a.js></SCRIPT><style nonce=\"abc\">p{}</style>\
+             <scripts><script nonce=\"abc\"/></head>"
+        );
+    }
+
+    #[test]
+    fn nonce_only_in_tags() {
+        let html = "<script>document.write('<script src=\"a.js\"><\\/script>'); // <style>\
+                    </script><!-- <script> --><p title=\"<style>\" class=a'b>\
+                    <textarea><script></textarea><STYLE>p{}</style ><script>";
+        assert_eq!(
+            inject_nonce(html, "abc"),
+            "<script nonce=\"abc\">document.write('<script src=\"a.js\"><\\/script>'); // <style>\
+             </script><!-- <script> --><p title=\"<style>\" class=a'b>\
+             <textarea><script></textarea><STYLE nonce=\"abc\">p{}</style >\
+             <script nonce=\"abc\">"
+        );
+    }
+
+    #[test]
+    fn random_nonce() {
+        let nonce = nonce();
+        assert_eq!(nonce.len(), 32);
+        assert!(nonce.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(nonce, super::nonce());
+    }
+
+    #[test]
+    fn config_script() {
+        let config = InjectConfig::new(r#"{"api":"</script>"}"#).variable("APP");
+        assert_eq!(
+            config.script_content(),
+            r#"window["APP"] = {"api":"<\/script>"};"#
+        );
+    }
+
+    #[test]
+    fn config_script_policy() {
+        let hash = "'sha256-abc'";
+        assert_eq!(
+            allow_script("default-src 'self'; object-src 'none'", hash).unwrap(),
+            "default-src 'self'; object-src 'none'; script-src 'self' 'sha256-abc'"
+        );
+        assert_eq!(
+            allow_script("script-src 'none'; style-src 'self'", hash).unwrap(),
+            "script-src 'sha256-abc'; style-src 'self'"
+        );
+        assert_eq!(
+            allow_script(
+                "script-src 'self' 'sha256-xyz'; script-src-elem 'self'",
+                hash
+            )
+            .unwrap(),
+            "script-src 'self' 'sha256-xyz'; script-src-elem 'self' 'sha256-abc'"
+        );
+        assert_eq!(allow_script("script-src 'unsafe-inline'", hash), None);
+        assert_eq!(allow_script("frame-ancestors 'none'", hash), None);
+        assert_eq!(
+            hash_source("alert('Hello, world.');"),
+            "'sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng='"
+        );
+    }
+
+    #[test]
+    fn config_script_position() {
+        let insert = |html: &str| {
+            let mut html = html.to_string();
+            html.insert_str(script_position(&html), "<script/>");
+            html
+        };
+        assert_eq!(
+            insert("<html><HEAD><title>App</title></HEAD></html>"),
+            "<html><HEAD><title>App</title><script/></HEAD></html>"
+        );
+        assert_eq!(
+            insert("<!DOCTYPE html><head lang=en><title>App</title><header></header>"),
+            "<!DOCTYPE html><head lang=en><script/><title>App</title><header></header>"
+        );
+        assert_eq!(
+            insert("<!doctype html><title>App</title><body><p>Hi</p>"),
+            "<!doctype html><title>App</title><script/><body><p>Hi</p>"
+        );
+        assert_eq!(
+            insert("<!doctype html><p>Hi</p>"),
+            "<!doctype html><script/><p>Hi</p>"
+        );
+        assert_eq!(insert("<p>Hi</p>"), "<script/><p>Hi</p>");
+    }
+}
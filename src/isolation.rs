@@ -0,0 +1,207 @@
+//! Resource isolation with Fetch Metadata request headers, see
+//! <https://www.w3.org/TR/fetch-metadata/>.
+
+use http::{HeaderMap, Method, header};
+
+use crate::core::glob::Glob;
+
+/// A policy rejecting the cross-site requests, to prevent hotlinking and cross-site leaks.
+///
+/// The requests are allowed if they are sent by the same site or directly by the user, according
+/// to the `Sec-Fetch-Site` header, if they are navigations, or if they match an allowed path. The
+/// responses vary on the Fetch Metadata headers.
+///
+/// For older clients not sending Fetch Metadata headers, the requests whose `Referer` has a
+/// different host than the request are rejected. Without the list of public suffixes the site of a
+/// host is not known, so the requests of the other subdomains of the same site are rejected too,
+/// while they are allowed with `Sec-Fetch-Site: same-site`.
+///
+/// # Example
+///
+/// ```
+/// # use tower_embed::isolation::ResourceIsolation;
+/// let policy = ResourceIsolation::new().allow_paths("fonts/**");
+/// ```
+#[derive(Clone, Debug)]
+pub struct ResourceIsolation {
+    allowed_paths: Vec<Glob>,
+    allow_navigations: bool,
+}
+
+impl Default for ResourceIsolation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceIsolation {
+    /// Create a new [`ResourceIsolation`] policy, allowing the navigations.
+    pub fn new() -> Self {
+        Self {
+            allowed_paths: Vec::new(),
+            allow_navigations: true,
+        }
+    }
+
+    /// Allow the cross-site requests to the paths matching the glob pattern, like `fonts/**`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid.
+    pub fn allow_paths(mut self, pattern: &str) -> Self {
+        let glob = Glob::parse(pattern).unwrap_or_else(|err| {
+            panic!("invalid resource isolation path pattern `{pattern}`: {err}")
+        });
+        self.allowed_paths.push(glob);
+        self
+    }
+
+    /// Allow the cross-site navigations, like links followed from other sites, the default is
+    /// `true`.
+    ///
+    /// Without navigations, the pages can be opened from other sites only at the allowed paths.
+    pub fn allow_navigations(mut self, allow_navigations: bool) -> Self {
+        self.allow_navigations = allow_navigations;
+        self
+    }
+
+    /// Returns true if the request is allowed by the policy.
+    pub(crate) fn allows(&self, request: &http::Request<()>) -> bool {
+        let headers = request.headers();
+        let allowed = match fetch_metadata(headers, "sec-fetch-site") {
+            Some("same-origin" | "same-site" | "none") => true,
+            Some(_) => self.allow_navigations && is_navigation(request),
+            // older clients, only the referer of another host is known to be cross-site
+            None => match (referer_host(headers), request_host(request)) {
+                (Some(referer), Some(host)) => referer.eq_ignore_ascii_case(host),
+                (Some(_), None) => false,
+                (None, _) => true,
+            },
+        };
+        allowed
+            || self
+                .allowed_paths
+                .iter()
+                .any(|glob| glob.matches(request.uri().path()))
+    }
+}
+
+/// Adds the Fetch Metadata headers to the `Vary` header of a response, the policy depends on them.
+pub(crate) fn vary(headers: &mut HeaderMap) {
+    headers.append(
+        header::VARY,
+        http::HeaderValue::from_static("sec-fetch-site, sec-fetch-mode, sec-fetch-dest"),
+    );
+}
+
+fn fetch_metadata<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Returns true if the request is a top-level navigation, excluding the plugin contents loaded
+/// with `<object>` and `<embed>`.
+fn is_navigation(request: &http::Request<()>) -> bool {
+    let headers = request.headers();
+    request.method() == Method::GET
+        && fetch_metadata(headers, "sec-fetch-mode") == Some("navigate")
+        && !matches!(
+            fetch_metadata(headers, "sec-fetch-dest"),
+            Some("object" | "embed")
+        )
+}
+
+fn referer_host(headers: &HeaderMap) -> Option<&str> {
+    let referer = headers.get(header::REFERER)?.to_str().ok()?;
+    let (_, rest) = referer.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    Some(
+        authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host),
+    )
+}
+
+fn request_host(request: &http::Request<()>) -> Option<&str> {
+    match request.headers().get(header::HOST) {
+        Some(host) => host.to_str().ok(),
+        None => request
+            .uri()
+            .authority()
+            .map(|authority| authority.as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> http::Request<()> {
+        let mut request = http::Request::builder()
+            .uri(uri)
+            .header(header::HOST, "example.com");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap()
+    }
+
+    #[test]
+    fn fetch_metadata_requests() {
+        let policy = ResourceIsolation::new().allow_paths("fonts/*");
+
+        assert!(policy.allows(&request("/app.js", &[("sec-fetch-site", "same-origin")])));
+        assert!(policy.allows(&request("/app.js", &[("sec-fetch-site", "none")])));
+        assert!(!policy.allows(&request("/app.zip", &[("sec-fetch-site", "cross-site")])));
+        assert!(policy.allows(&request(
+            "/fonts/a.woff2",
+            &[("sec-fetch-site", "cross-site")]
+        )));
+
+        let navigation = [
+            ("sec-fetch-site", "cross-site"),
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-dest", "document"),
+        ];
+        assert!(policy.allows(&request("/index.html", &navigation)));
+        let policy = policy.allow_navigations(false);
+        assert!(!policy.allows(&request("/index.html", &navigation)));
+
+        let embed = [
+            ("sec-fetch-site", "cross-site"),
+            ("sec-fetch-mode", "navigate"),
+            ("sec-fetch-dest", "embed"),
+        ];
+        assert!(!ResourceIsolation::new().allows(&request("/app.pdf", &embed)));
+    }
+
+    #[test]
+    fn referer_fallback() {
+        let policy = ResourceIsolation::new();
+
+        assert!(policy.allows(&request("/app.zip", &[])));
+        assert!(policy.allows(&request(
+            "/app.zip",
+            &[("referer", "https://Example.com/download")]
+        )));
+        assert!(!policy.allows(&request(
+            "/app.zip",
+            &[("referer", "https://other.com/page?from=example.com")]
+        )));
+        // the site of the hosts is not known
+        assert!(!policy.allows(&request(
+            "/app.zip",
+            &[("referer", "https://www.example.com/")]
+        )));
+    }
+
+    #[test]
+    fn vary_on_fetch_metadata() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::VARY, http::HeaderValue::from_static("origin"));
+        vary(&mut headers);
+        assert_eq!(
+            headers.get_all(header::VARY).iter().collect::<Vec<_>>(),
+            ["origin", "sec-fetch-site, sec-fetch-mode, sec-fetch-dest"]
+        );
+    }
+}
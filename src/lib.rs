@@ -5,7 +5,21 @@
 //! - `Content-Type` header generation based on file MIME type guessed from extension.
 //! - `ETag` header generation and validation.
 //! - `Last-Modified` header generation and validation.
-//! - Customizable 404 page.
+//! - Redirects to canonical directory URLs, with configurable trailing slash policy.
+//! - Clean URLs, serving `about.html` at `/about`.
+//! - Declarative redirect and rewrite rules.
+//! - Custom headers and redirects from `_headers` and `_redirects` files.
+//! - Content fingerprinting with immutable caching.
+//! - Compile-time checked asset paths.
+//! - Entry points resolution from Vite, webpack and Trunk build manifests.
+//! - Subresource Integrity metadata computed at compile time.
+//! - `Content-Security-Policy` hashes of inline scripts and styles computed at compile time.
+//! - Assets stored compressed, sent as they are or decompressed on demand.
+//! - Runtime HTML transforms, injecting CSP nonces and configuration.
+//! - Security header presets, like `nosniff` and cross-origin isolation.
+//! - Cross-Origin Resource Sharing, with preflight requests.
+//! - Resource isolation with Fetch Metadata headers, rejecting hotlinking.
+//! - Customizable error pages, with `application/problem+json` responses for API clients.
 //!
 //! In `debug` mode, assets are served directly from the filesystem to facilitate rapid
 //! development. Both `ETag` and `Last-Modified` headers are not generated in this mode.
@@ -43,6 +57,7 @@
 compile_error!("Only tokio runtime is supported, and it is required to use `tower-embed`.");
 
 use std::{
+    borrow::Cow,
     convert::Infallible,
     marker::PhantomData,
     pin::Pin,
@@ -50,8 +65,17 @@ use std::{
     task::{Context, Poll},
 };
 
+use self::{
+    cors::Cors,
+    error_page::ErrorPages,
+    isolation::ResourceIsolation,
+    rules::{HeaderRule, Outcome, Rule},
+    security::SecurityHeaders,
+    storage::DecompressionCache,
+};
+
 #[doc(inline)]
-pub use tower_embed_impl::Embed;
+pub use tower_embed_impl::{Embed, embed};
 
 #[doc(inline)]
 pub use tower_embed_core as core;
@@ -59,8 +83,96 @@ pub use tower_embed_core as core;
 #[doc(inline)]
 pub use tower_embed_core::{Embed, http::Body};
 
+pub use self::directory::TrailingSlash;
+
+/// Returns the typed [`Asset`](core::Asset) handle of a file embedded by a type deriving
+/// [`Embed`], checking at compile time that the file exists.
+///
+/// A path not matching any file is a compile error, suggesting the closest existing file.
+///
+/// ```ignore
+/// let page = tower_embed::asset!(Assets, "404.html");
+/// let service = ServeEmbed::builder()
+///     .not_found_service(Assets::not_found_page(page))
+///     .build::<Assets>();
+/// ```
+#[macro_export]
+macro_rules! asset {
+    ($embed:ty, $path:expr $(,)?) => {
+        const { <$embed>::__asset($path) }
+    };
+}
+
+mod clean_url;
+pub mod cors;
+mod directory;
+mod error_page;
 #[doc(hidden)]
 pub mod file;
+pub mod html;
+pub mod isolation;
+mod rules;
+pub mod security;
+mod storage;
+
+/// Checks of the features decompressing the assets, required by the generated code.
+#[doc(hidden)]
+pub mod __storage {
+    pub const fn brotli() {
+        #[cfg(not(feature = "brotli"))]
+        panic!("`storage = \"brotli\"` requires the `brotli` feature of `tower-embed`");
+    }
+
+    pub const fn zstd() {
+        #[cfg(not(feature = "zstd"))]
+        panic!("`storage = \"zstd\"` requires the `zstd` feature of `tower-embed`");
+    }
+}
+
+/// Entity tags of the virtual files and of the transformed pages, their content is known only when
+/// the generated code is compiled or at runtime.
+#[doc(hidden)]
+pub mod __etag {
+    use crate::core::{
+        hasher::{self, Strength},
+        headers::ETag,
+    };
+
+    pub fn rapidhash(content: &[u8], strength: Strength) -> ETag {
+        crate::core::etag_with::<hasher::RapidHash>(content, strength)
+    }
+
+    pub fn sha256(content: &[u8], strength: Strength) -> ETag {
+        #[cfg(feature = "sha256")]
+        return crate::core::etag_with::<hasher::Sha256>(content, strength);
+        #[cfg(not(feature = "sha256"))]
+        {
+            let _ = (content, strength);
+            unreachable!("checked by `require_sha256`")
+        }
+    }
+
+    pub fn blake3(content: &[u8], strength: Strength) -> ETag {
+        #[cfg(feature = "blake3")]
+        return crate::core::etag_with::<hasher::Blake3>(content, strength);
+        #[cfg(not(feature = "blake3"))]
+        {
+            let _ = (content, strength);
+            unreachable!("checked by `require_blake3`")
+        }
+    }
+
+    /// Checks of the features, required by the generated code.
+    pub const fn require_sha256() {
+        #[cfg(not(feature = "sha256"))]
+        panic!("`etag = \"sha256\"` requires the `sha256` feature of `tower-embed`");
+    }
+
+    pub const fn require_blake3() {
+        #[cfg(not(feature = "blake3"))]
+        panic!("`etag = \"blake3\"` requires the `blake3` feature of `tower-embed`");
+    }
+}
 
 /// Response future of [`ServeEmbed`]
 pub struct ResponseFuture(ResponseFutureInner);
@@ -88,18 +200,45 @@ impl Future for ResponseFuture {
 /// Service that serves files from embedded assets.
 pub struct ServeEmbed<E = ()> {
     _embed: PhantomData<E>,
-    /// Fallback service for handling 404 Not Found errors.
-    not_found_service: Option<NotFoundService>,
+    config: Arc<Config>,
 }
 
-type NotFoundService =
-    tower::util::BoxCloneSyncService<http::Request<()>, http::Response<Body>, Infallible>;
+/// Configuration of [`ServeEmbed`] service.
+#[derive(Default)]
+struct Config {
+    /// Services rendering custom error pages.
+    error_pages: ErrorPages,
+    /// Policy for the trailing slash of directory URLs.
+    trailing_slash: TrailingSlash,
+    /// Redirect requests to index files to their directory.
+    redirect_index: bool,
+    /// Serve files at URLs without extension.
+    clean_urls: bool,
+    /// Redirect requests to files to their clean URL.
+    redirect_clean_urls: bool,
+    /// Rules redirecting or rewriting requests, in order of evaluation.
+    rules: Vec<Rule>,
+    /// Rules adding custom headers to responses.
+    header_rules: Vec<HeaderRule>,
+    /// Cache of the assets decompressed for clients not accepting their encoding.
+    decompression_cache: Option<DecompressionCache>,
+    /// Transformations of the HTML pages, in order of application.
+    html_transforms: Vec<html::BoxHtmlTransform>,
+    /// Set the `Content-Security-Policy` header computed at compile time.
+    content_security_policy: bool,
+    /// Security headers set according to the content type of responses.
+    security_headers: Option<SecurityHeaders>,
+    /// Cross-Origin Resource Sharing of the assets.
+    cors: Option<Cors>,
+    /// Policy rejecting the cross-site requests.
+    resource_isolation: Option<ResourceIsolation>,
+}
 
 impl<E> Clone for ServeEmbed<E> {
     fn clone(&self) -> Self {
         Self {
             _embed: PhantomData,
-            not_found_service: self.not_found_service.clone(),
+            config: Arc::clone(&self.config),
         }
     }
 }
@@ -117,6 +256,13 @@ impl<E: Embed> ServeEmbed<E> {
     }
 }
 
+impl<E: Embed> From<E> for ServeEmbed<E> {
+    /// Create a new [`ServeEmbed`] service of the value, like the one returned by [`embed!`].
+    fn from(_: E) -> Self {
+        Self::new()
+    }
+}
+
 impl ServeEmbed<()> {
     /// Create a new [`ServeEmbedBuilder`] to customize a new service instance.
     pub fn builder() -> ServeEmbedBuilder {
@@ -138,25 +284,193 @@ where
 
     fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
         let req = req.map(|_| ());
-        let not_found_service = self.not_found_service.clone();
-        ResponseFuture::new(async move {
-            let response =
-                if req.method() != http::Method::GET && req.method() != http::Method::HEAD {
-                    method_not_allowed()
-                } else {
-                    let path = req.uri().path().trim_start_matches('/');
-                    tracing::trace!("Serving embedded resource '{path}'");
-                    handle_request(E::get(path), req, not_found_service).await
-                };
-            Ok(response)
-        })
+        let config = Arc::clone(&self.config);
+        ResponseFuture::new(async move { Ok(serve::<E>(&config, req).await) })
     }
 }
 
+async fn serve<E: Embed>(config: &Config, req: http::Request<()>) -> http::Response<Body> {
+    let cors = config
+        .cors
+        .as_ref()
+        .filter(|cors| cors.matches(req.uri().path()));
+    if req.method() == http::Method::OPTIONS
+        && let Some(cors) = cors
+    {
+        return cors.preflight(&req);
+    }
+    if req.method() != http::Method::GET && req.method() != http::Method::HEAD {
+        let mut response = config
+            .error_pages
+            .respond(http::StatusCode::METHOD_NOT_ALLOWED, req)
+            .await;
+        if cors.is_some() {
+            response.headers_mut().insert(
+                http::header::ALLOW,
+                http::HeaderValue::from_static("GET, HEAD, OPTIONS"),
+            );
+        }
+        return response;
+    }
+    if let Some(resource_isolation) = &config.resource_isolation
+        && !resource_isolation.allows(&req)
+    {
+        tracing::trace!("Rejected cross-site request for '{}'", req.uri().path());
+        let mut response = config
+            .error_pages
+            .respond(http::StatusCode::FORBIDDEN, req)
+            .await;
+        isolation::vary(response.headers_mut());
+        return response;
+    }
+
+    let path = (!config.header_rules.is_empty()).then(|| req.uri().path().to_string());
+    let request = (!config.html_transforms.is_empty()).then(|| html::request_copy(&req));
+    let origin = cors.and_then(|_| req.headers().get(http::header::ORIGIN).cloned());
+    let mut response = serve_embedded::<E>(config, req).await;
+    if let Some(security_headers) = &config.security_headers {
+        security_headers.apply(response.headers_mut());
+    }
+    if let Some(cors) = cors {
+        cors.apply(origin.as_ref(), response.headers_mut());
+    }
+    if config.resource_isolation.is_some() {
+        isolation::vary(response.headers_mut());
+    }
+    if let Some(path) = path
+        && !(response.status().is_client_error() || response.status().is_server_error())
+    {
+        rules::apply_headers(&config.header_rules, &path, response.headers_mut());
+    }
+
+    // the pages are transformed last, the headers of the transformed content replace the others
+    if let Some(request) = request {
+        response = match html::apply(&config.html_transforms, &request, response, E::etag).await {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::error!("Failed to read HTML page '{}': {err}", request.uri().path());
+                config
+                    .error_pages
+                    .respond(http::StatusCode::INTERNAL_SERVER_ERROR, request)
+                    .await
+            }
+        };
+    }
+    response
+}
+
+async fn serve_embedded<E: Embed>(config: &Config, req: http::Request<()>) -> http::Response<Body> {
+    let mut rewrite = None;
+    let mut fallback = None;
+    let mut shadowed = None;
+    {
+        let mut matches = rules::apply(&config.rules, req.uri().path());
+        match matches.next() {
+            Some((rule, outcome)) if !rule.is_forced() => {
+                // skipped when an asset is found, then the next forced rule is applied
+                fallback = Some(outcome);
+                shadowed = matches.find_map(|(rule, outcome)| rule.is_forced().then_some(outcome));
+            }
+            Some((_, Outcome::Redirect(status, location))) => {
+                return redirect_response(status, req.uri(), &location);
+            }
+            Some((_, Outcome::Rewrite(target, status))) => rewrite = Some((target, status)),
+            None => {}
+        }
+    }
+
+    let (path, mut status) = match &rewrite {
+        Some((target, status)) => (target.trim_start_matches('/'), *status),
+        None => (
+            req.uri().path().trim_start_matches('/'),
+            http::StatusCode::OK,
+        ),
+    };
+
+    // canonical URLs are relative to the requested path, they are skipped for rewritten paths
+    if rewrite.is_none()
+        && let Some(location) =
+            directory::canonical_location::<E>(path, config.trailing_slash, config.redirect_index)
+    {
+        tracing::trace!("Redirecting '{path}' to canonical URL '{location}'");
+        return redirect_response(http::StatusCode::PERMANENT_REDIRECT, req.uri(), &location);
+    }
+    if rewrite.is_none()
+        && config.clean_urls
+        && config.redirect_clean_urls
+        && let Some(location) = clean_url::clean_location::<E>(path)
+    {
+        tracing::trace!("Redirecting '{path}' to clean URL '{location}'");
+        return redirect_response(http::StatusCode::PERMANENT_REDIRECT, req.uri(), &location);
+    }
+
+    tracing::trace!("Serving embedded resource '{path}'");
+    let mut served_path = Cow::Borrowed(path);
+    let mut embedded = E::get(path).await;
+    if config.clean_urls
+        && matches!(&embedded, Err(err) if is_not_found(err))
+        && let Some(clean_path) = E::clean_url(path)
+    {
+        tracing::trace!("Serving embedded resource '{clean_path}' for clean URL");
+        embedded = E::get(&clean_path).await;
+        served_path = Cow::Owned(clean_path.into_owned());
+    }
+    let outcome = match &embedded {
+        Err(err) if is_not_found(err) => fallback,
+        Ok(_) => shadowed,
+        Err(_) => None,
+    };
+    if let Some(outcome) = outcome {
+        match outcome {
+            Outcome::Redirect(status, location) => {
+                return redirect_response(status, req.uri(), &location);
+            }
+            Outcome::Rewrite(target, rewrite_status) => {
+                tracing::trace!("Serving embedded resource '{target}' for '{path}'");
+                embedded = E::get(target.trim_start_matches('/')).await;
+                served_path = Cow::Owned(target.trim_start_matches('/').to_string());
+                status = rewrite_status;
+            }
+        }
+    }
+    let policy = config
+        .content_security_policy
+        .then(|| E::content_security_policy(&served_path))
+        .flatten()
+        .map(http::HeaderValue::from_static);
+
+    if status == http::StatusCode::OK {
+        let mut response = handle_request(embedded, req, config).await;
+        if response.status() == http::StatusCode::OK
+            && let Some(policy) = policy
+        {
+            response
+                .headers_mut()
+                .insert(http::header::CONTENT_SECURITY_POLICY, policy);
+        }
+        return response;
+    }
+
+    // validators of the rewritten resource do not apply to an error response
+    let mut req = req;
+    req.headers_mut().remove(http::header::IF_NONE_MATCH);
+    req.headers_mut().remove(http::header::IF_MODIFIED_SINCE);
+    let mut response = handle_request(embedded, req, config).await;
+    if response.status() == http::StatusCode::OK {
+        *response.status_mut() = status;
+        response.headers_mut().remove(http::header::ETAG);
+        response.headers_mut().remove(http::header::LAST_MODIFIED);
+        response
+            .headers_mut()
+            .extend(policy.map(|policy| (http::header::CONTENT_SECURITY_POLICY, policy)));
+    }
+    response
+}
+
 /// Builder for [`ServeEmbed`] service.
 #[derive(Default)]
 pub struct ServeEmbedBuilder {
-    not_found_service: Option<NotFoundService>,
+    config: Config,
 }
 
 impl ServeEmbedBuilder {
@@ -166,7 +480,11 @@ impl ServeEmbedBuilder {
     }
 
     /// Set the fallback service.
-    pub fn not_found_service<S>(mut self, service: S) -> Self
+    ///
+    /// This is a shorthand for [`error_page`] with `404 Not Found` status code.
+    ///
+    /// [`error_page`]: ServeEmbedBuilder::error_page
+    pub fn not_found_service<S>(self, service: S) -> Self
     where
         S: tower::Service<http::Request<()>, Response = http::Response<Body>, Error = Infallible>
             + Send
@@ -175,24 +493,280 @@ impl ServeEmbedBuilder {
             + 'static,
         S::Future: Send + 'static,
     {
-        self.not_found_service = Some(tower::util::BoxCloneSyncService::new(service));
+        self.error_page(http::StatusCode::NOT_FOUND, service)
+    }
+
+    /// Set the service rendering the page of error responses with the given status code.
+    ///
+    /// The status code of the response returned by the service is replaced with `status`. Clients
+    /// that prefer JSON, according to the `Accept` header, receive an `application/problem+json`
+    /// body instead.
+    pub fn error_page<S>(mut self, status: http::StatusCode, service: S) -> Self
+    where
+        S: tower::Service<http::Request<()>, Response = http::Response<Body>, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        self.config
+            .error_pages
+            .insert(status, tower::util::BoxCloneSyncService::new(service));
+        self
+    }
+
+    /// Set the policy for the trailing slash of directory URLs.
+    ///
+    /// The default is [`TrailingSlash::Redirect`].
+    pub fn trailing_slash(mut self, trailing_slash: TrailingSlash) -> Self {
+        self.config.trailing_slash = trailing_slash;
+        self
+    }
+
+    /// Redirect requests to index files, like `/dir/index.html`, to their directory.
+    ///
+    /// The location of the redirect follows the [`TrailingSlash`] policy, the default is `false`.
+    pub fn redirect_index(mut self, redirect_index: bool) -> Self {
+        self.config.redirect_index = redirect_index;
+        self
+    }
+
+    /// Serve files at URLs without extension, like `about.html` at `/about`.
+    ///
+    /// The file is searched only if no file matches the requested path exactly. The extensions
+    /// that can be omitted are configured with `#[embed(extension = "...")]`, see
+    /// [`Embed::clean_url`]. The default is `false`.
+    pub fn clean_urls(mut self, clean_urls: bool) -> Self {
+        self.config.clean_urls = clean_urls;
+        self
+    }
+
+    /// Redirect requests to files, like `/about.html`, to their clean URL.
+    ///
+    /// This is effective only if clean URLs are enabled, the default is `false`.
+    pub fn redirect_clean_urls(mut self, redirect_clean_urls: bool) -> Self {
+        self.config.redirect_clean_urls = redirect_clean_urls;
+        self
+    }
+
+    /// Redirect the requests matching the pattern to the target.
+    ///
+    /// The pattern is made of segments, each one is either a literal or a placeholder `:name`, the
+    /// last segment can be a splat `*` matching the rest of the path. The captured values are
+    /// substituted into the target, the splat as `:splat`. The query string of the request is
+    /// preserved.
+    ///
+    /// Rules are evaluated in the order they are added and only the first matching rule is
+    /// applied. Redirects are applied before looking up the embedded assets, rewrites only when no
+    /// asset is found at the requested path and are skipped otherwise, like the rules of
+    /// `_redirects` files without `!`. The rules defined by [`Embed::redirect_rules`] are evaluated
+    /// after the ones added to the builder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid or the status code is not `301`, `302`, `303`, `307` or
+    /// `308`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tower_embed::ServeEmbed;
+    /// let builder = ServeEmbed::builder().redirect(
+    ///     "/img/*",
+    ///     "/images/:splat",
+    ///     http::StatusCode::MOVED_PERMANENTLY,
+    /// );
+    /// ```
+    pub fn redirect(mut self, from: &str, to: &str, status: http::StatusCode) -> Self {
+        self.config.rules.push(Rule::redirect(from, to, status));
+        self
+    }
+
+    /// Serve the target in place of the requests matching the pattern.
+    ///
+    /// The syntax of pattern and target is the same of [`redirect`], the target must be an
+    /// absolute path of the embedded assets. The rule is applied only when no asset is found at
+    /// the requested path, so that a single page application is served with its assets:
+    ///
+    /// ```
+    /// # use tower_embed::ServeEmbed;
+    /// let builder = ServeEmbed::builder().rewrite("/app/*", "/app/index.html");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid or the target is not an absolute path.
+    ///
+    /// [`redirect`]: ServeEmbedBuilder::redirect
+    pub fn rewrite(mut self, from: &str, to: &str) -> Self {
+        self.config.rules.push(Rule::rewrite(from, to));
+        self
+    }
+
+    /// Cache the assets decompressed for clients not accepting their encoding, up to `max_bytes`.
+    ///
+    /// Assets stored compressed with `#[embed(storage = "...")]` are sent as they are to clients
+    /// accepting their encoding, and decompressed on the fly for the others. With the cache, the
+    /// least recently used assets are evicted when the size of the decompressed assets exceeds
+    /// `max_bytes`, larger assets are not cached. The default is no cache.
+    pub fn decompression_cache(mut self, max_bytes: usize) -> Self {
+        self.config.decompression_cache = Some(DecompressionCache::new(max_bytes));
+        self
+    }
+
+    /// Set the `Content-Security-Policy` header of the HTML pages, computed at compile time.
+    ///
+    /// The policy allows the inline scripts and styles of each page with their hashes, and it is
+    /// configured with `#[embed(csp = "...")]`, see [`Embed::content_security_policy`]. In debug
+    /// builds the pages can change without recompiling, so no header is set. The default is
+    /// `false`.
+    ///
+    /// The scripts inserted at runtime are not allowed by the policy, except the ones of
+    /// [`InjectConfig`](html::InjectConfig) and [`CspNonce`](html::CspNonce) that extend it.
+    pub fn content_security_policy(mut self, content_security_policy: bool) -> Self {
+        self.config.content_security_policy = content_security_policy;
+        self
+    }
+
+    /// Set the security headers of the responses, according to their content type.
+    ///
+    /// The headers are a [`Preset`](security::Preset) or a custom [`SecurityHeaders`] set, and
+    /// the sets of multiple calls are combined. A header already in the response, like the
+    /// [`content_security_policy`] computed at compile time, is not replaced, and the custom
+    /// headers of [`Embed::header_rules`] replace the security headers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tower_embed::{ServeEmbed, security::Preset};
+    /// let builder = ServeEmbed::builder()
+    ///     .security_headers(Preset::Strict)
+    ///     .security_headers(Preset::CrossOriginIsolated);
+    /// ```
+    ///
+    /// [`content_security_policy`]: ServeEmbedBuilder::content_security_policy
+    pub fn security_headers(mut self, headers: impl Into<SecurityHeaders>) -> Self {
+        let headers = match self.config.security_headers.take() {
+            Some(current) => current.with(headers),
+            None => headers.into(),
+        };
+        self.config.security_headers = Some(headers);
+        self
+    }
+
+    /// Share the assets with other origins, with Cross-Origin Resource Sharing.
+    ///
+    /// The responses to the allowed origins have the `Access-Control-Allow-Origin` header, and the
+    /// `OPTIONS` requests to the shared assets are answered as preflight requests, so the `Allow`
+    /// header of their `405 Method Not Allowed` responses lists `OPTIONS`. The custom headers of
+    /// [`Embed::header_rules`] replace the CORS headers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tower_embed::{ServeEmbed, cors::Cors};
+    /// let builder = ServeEmbed::builder().cors(
+    ///     Cors::origins(["https://example.com"]).paths("fonts/*.woff2"),
+    /// );
+    /// ```
+    pub fn cors(mut self, cors: Cors) -> Self {
+        self.config.cors = Some(cors);
+        self
+    }
+
+    /// Reject the cross-site requests with `403 Forbidden`, according to the policy.
+    ///
+    /// The requests are checked before looking up the embedded assets, the assets shared with
+    /// [`cors`] should be allowed by the policy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tower_embed::{ServeEmbed, isolation::ResourceIsolation};
+    /// let builder = ServeEmbed::builder()
+    ///     .resource_isolation(ResourceIsolation::new().allow_paths("images/**"));
+    /// ```
+    ///
+    /// [`cors`]: ServeEmbedBuilder::cors
+    pub fn resource_isolation(mut self, resource_isolation: ResourceIsolation) -> Self {
+        self.config.resource_isolation = Some(resource_isolation);
+        self
+    }
+
+    /// Add a transformation of the HTML pages, applied after the ones already added.
+    ///
+    /// The transformations receive the request and the decoded content of the `text/html`
+    /// embedded files, with the headers of the response. The `Content-Length` and `ETag` headers
+    /// are computed from the transformed page, and the conditional requests are evaluated against
+    /// it, the `Last-Modified` header is omitted. The pages rendered by the services of
+    /// [`error_page`] are not transformed.
+    ///
+    /// The [`html`] module provides the injection of a [CSP nonce](html::CspNonce) and of a
+    /// [runtime configuration](html::InjectConfig).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use tower_embed::{ServeEmbed, html::{CspNonce, InjectConfig}};
+    /// let builder = ServeEmbed::builder()
+    ///     .html_transform(InjectConfig::new(r#"{"api":"https://api.example.com"}"#))
+    ///     .html_transform(CspNonce::new());
+    /// ```
+    ///
+    /// [`error_page`]: ServeEmbedBuilder::error_page
+    pub fn html_transform<T: html::HtmlTransform>(mut self, transform: T) -> Self {
+        self.config.html_transforms.push(Arc::new(transform));
         self
     }
 
     /// Build the [`ServeEmbed`] service.
-    pub fn build<E: Embed>(self) -> ServeEmbed<E> {
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rules defined by [`Embed::redirect_rules`] or [`Embed::header_rules`] are
+    /// invalid.
+    pub fn build<E: Embed>(mut self) -> ServeEmbed<E> {
+        let redirect_rules = E::redirect_rules().iter().map(Rule::from_static);
+        self.config.rules.extend(redirect_rules);
+        let header_rules = E::header_rules().iter().map(HeaderRule::from_static);
+        self.config.header_rules.extend(header_rules);
+
         ServeEmbed {
             _embed: PhantomData,
-            not_found_service: self.not_found_service,
+            config: Arc::new(self.config),
         }
     }
+
+    /// Build the [`ServeEmbed`] service of the value, like the one returned by [`embed!`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rules of the embedded assets are invalid, see [`build`].
+    ///
+    /// [`build`]: ServeEmbedBuilder::build
+    pub fn build_with<E: Embed>(self, embed: E) -> ServeEmbed<E> {
+        let _ = embed;
+        self.build::<E>()
+    }
 }
 
 /// Extension trait for [`Embed`].
 pub trait EmbedExt: Embed + Sized {
     /// Returns a service that serves a custom not found page.
-    fn not_found_page(path: &str) -> NotFoundPage<Self> {
-        NotFoundPage::new(path.to_string())
+    ///
+    /// The page is a path or an [`Asset`](core::Asset) handle, created with [`asset!`]. The
+    /// service can be used to render any error page, see [`ServeEmbedBuilder::error_page`].
+    fn not_found_page(path: impl core::IntoAssetPath<Self>) -> NotFoundPage<Self> {
+        NotFoundPage::new(Page::Fixed(path.into_asset_path()))
+    }
+
+    /// Returns a service that serves the not found page closest to the requested path.
+    ///
+    /// The name of the page is configured with `#[embed(not_found = "...")]`, see
+    /// [`Embed::nearest_not_found`].
+    fn nearest_not_found_page() -> NotFoundPage<Self> {
+        NotFoundPage::new(Page::Nearest)
     }
 }
 
@@ -209,11 +783,18 @@ impl<E> Clone for NotFoundPage<E> {
 
 struct NotFoundPageInner<E> {
     _embed: PhantomData<E>,
-    page: String,
+    page: Page,
+}
+
+enum Page {
+    /// The page at a fixed path.
+    Fixed(Cow<'static, str>),
+    /// The page closest to the requested path.
+    Nearest,
 }
 
 impl<E> NotFoundPage<E> {
-    fn new(page: String) -> Self {
+    fn new(page: Page) -> Self {
         Self(Arc::new(NotFoundPageInner {
             _embed: PhantomData,
             page,
@@ -234,116 +815,429 @@ where
     }
 
     fn call(&mut self, req: http::Request<()>) -> Self::Future {
-        let embedded = E::get(&self.0.page);
-        ResponseFuture::new(async move { Ok(handle_request(embedded, req, None).await) })
+        let page = match &self.0.page {
+            Page::Fixed(page) => Some(page.clone()),
+            Page::Nearest => E::nearest_not_found(req.uri().path()),
+        };
+        ResponseFuture::new(async move {
+            let response = match page {
+                Some(page) => handle_request(E::get(&page).await, req, &Config::default()).await,
+                None => http::Response::builder()
+                    .status(http::StatusCode::NOT_FOUND)
+                    .body(Body::empty())
+                    .unwrap(),
+            };
+            Ok(response)
+        })
     }
 }
 
-async fn handle_request<F>(
-    embedded: F,
+async fn handle_request(
+    embedded: std::io::Result<core::Embedded>,
     request: http::Request<()>,
-    not_found_service: Option<NotFoundService>,
-) -> http::Response<Body>
-where
-    F: Future<Output = std::io::Result<core::Embedded>> + Send,
-{
+    config: &Config,
+) -> http::Response<Body> {
     use core::headers::{self, HeaderMapExt};
 
     let path = request.uri().path().trim_start_matches('/');
-    let core::Embedded { content, metadata } = match embedded.await {
+    let core::Embedded { content, metadata } = match embedded {
         Ok(embedded) => embedded,
-        Err(err)
-            if err.kind() == std::io::ErrorKind::NotFound
-                || err.kind() == std::io::ErrorKind::NotADirectory =>
-        {
+        Err(err) if is_not_found(&err) => {
             tracing::trace!("Embedded resource not found: '{path}'");
-            return not_found_response(request, not_found_service).await;
+            return config
+                .error_pages
+                .respond(http::StatusCode::NOT_FOUND, request)
+                .await;
         }
         Err(err) => {
             tracing::error!("Failed to get embedded resource '{path}': {err}");
-            return server_error_response(err);
+            return config
+                .error_pages
+                .respond(http::StatusCode::INTERNAL_SERVER_ERROR, request)
+                .await;
         }
     };
 
+    // the transformed pages are decoded, their validators are computed from the transformed content
+    let transformable =
+        (!config.html_transforms.is_empty() && html::is_html(&metadata.content_type)).then(|| {
+            html::Transformable(metadata.etag.as_ref().map(|etag| match etag.is_weak() {
+                true => core::hasher::Strength::Weak,
+                false => core::hasher::Strength::Strong,
+            }))
+        });
+
+    // the content is sent as it is stored to clients accepting its encoding
+    let accept_encoding = request.headers().typed_get::<headers::AcceptEncoding>();
+    let content_encoding = metadata
+        .content_encoding
+        .filter(|_| transformable.is_none())
+        .filter(|encoding| accept_encoding.is_some_and(|accept| accept.accepts(*encoding)));
+    let etag = match (&metadata.etag, content_encoding) {
+        (Some(etag), Some(encoding)) => encoded_etag(etag, encoding),
+        (etag, _) => etag.clone(),
+    };
+    let vary = metadata
+        .content_encoding
+        .filter(|_| transformable.is_none())
+        .map(|_| {
+            (
+                http::header::VARY,
+                http::HeaderValue::from_static("accept-encoding"),
+            )
+        });
+
     let if_none_match = request.headers().typed_get::<headers::IfNoneMatch>();
-    if let Some(if_none_match) = if_none_match
-        && let Some(etag) = &metadata.etag
+    if transformable.is_none()
+        && let Some(if_none_match) = if_none_match
+        && let Some(etag) = &etag
         && !if_none_match.condition_passes(etag)
     {
         tracing::trace!("ETag match for embedded resource '{path}'");
-        return not_modified_response();
+        let mut response = not_modified_response();
+        response.headers_mut().extend(vary);
+        return response;
     }
 
     let if_modified_since = request.headers().typed_get::<headers::IfModifiedSince>();
-    if let Some(if_modified_since) = if_modified_since
+    if transformable.is_none()
+        && let Some(if_modified_since) = if_modified_since
         && let Some(last_modified) = &metadata.last_modified
         && !if_modified_since.condition_passes(last_modified)
     {
         tracing::trace!("Last-Modified match for embedded resource '{path}'");
-        return not_modified_response();
+        let mut response = not_modified_response();
+        response.headers_mut().extend(vary);
+        return response;
     }
 
+    let content = match (metadata.content_encoding, content_encoding) {
+        (Some(encoding), None) => {
+            let decoded = match (&config.decompression_cache, &metadata.etag) {
+                (Some(cache), Some(etag)) => cache.decode(etag.value(), content, encoding).await,
+                _ => storage::decode(content, encoding),
+            };
+            match decoded {
+                Ok(content) => content,
+                Err(err) => {
+                    tracing::error!("Failed to decompress embedded resource '{path}': {err}");
+                    return config
+                        .error_pages
+                        .respond(http::StatusCode::INTERNAL_SERVER_ERROR, request)
+                        .await;
+                }
+            }
+        }
+        _ => content,
+    };
+
     let mut response = http::Response::builder()
         .status(http::StatusCode::OK)
         .body(Body::stream(content))
         .unwrap();
 
     response.headers_mut().typed_insert(metadata.content_type);
-    if let Some(etag) = metadata.etag {
+    if let Some(encoding) = content_encoding {
+        response.headers_mut().typed_insert(encoding);
+    }
+    response.headers_mut().extend(vary);
+    if let Some(etag) = etag {
         response.headers_mut().typed_insert(etag);
     }
     if let Some(last_modified) = metadata.last_modified {
         response.headers_mut().typed_insert(last_modified);
     }
+    if metadata.immutable {
+        response.headers_mut().insert(
+            http::header::CACHE_CONTROL,
+            http::HeaderValue::from_static("public, max-age=31536000, immutable"),
+        );
+    }
+    if let Some(transformable) = transformable {
+        response.extensions_mut().insert(transformable);
+    }
 
     response
 }
 
-async fn not_found_response(
-    request: http::Request<()>,
-    mut not_found_service: Option<NotFoundService>,
-) -> http::Response<Body> {
-    use tower::ServiceExt;
+/// Returns the entity tag of the encoded representation, distinct from the decoded one.
+fn encoded_etag(
+    etag: &core::headers::ETag,
+    encoding: core::headers::ContentEncoding,
+) -> Option<core::headers::ETag> {
+    let value = format!("{}-{}", etag.value(), encoding.as_str());
+    if etag.is_weak() {
+        core::headers::ETag::weak(&value)
+    } else {
+        core::headers::ETag::new(&value)
+    }
+}
 
-    let mut response = match not_found_service.take() {
-        Some(service) => {
-            let service = service.ready_oneshot().await.unwrap();
-            service.oneshot(request).await.unwrap()
-        }
-        None => http::Response::builder()
-            .status(http::StatusCode::NOT_FOUND)
-            .body(Body::empty())
-            .unwrap(),
-    };
-    response.headers_mut().insert(
-        http::header::CACHE_CONTROL,
-        http::HeaderValue::from_static("no-store"),
-    );
-    response
+fn is_not_found(err: &std::io::Error) -> bool {
+    err.kind() == std::io::ErrorKind::NotFound || err.kind() == std::io::ErrorKind::NotADirectory
 }
 
-fn not_modified_response() -> http::Response<Body> {
+fn redirect_response(
+    status: http::StatusCode,
+    uri: &http::Uri,
+    location: &str,
+) -> http::Response<Body> {
+    let location = match uri.query() {
+        Some(query) if location.contains('?') => format!("{location}&{query}"),
+        Some(query) => format!("{location}?{query}"),
+        None => location.to_string(),
+    };
+
     http::Response::builder()
-        .status(http::StatusCode::NOT_MODIFIED)
+        .status(status)
+        .header(http::header::LOCATION, location)
         .body(Body::empty())
         .unwrap()
 }
 
-fn method_not_allowed() -> http::Response<Body> {
+fn not_modified_response() -> http::Response<Body> {
     http::Response::builder()
-        .header(
-            http::header::ALLOW,
-            http::HeaderValue::from_static("GET, HEAD"),
-        )
-        .status(http::StatusCode::METHOD_NOT_ALLOWED)
+        .status(http::StatusCode::NOT_MODIFIED)
         .body(Body::empty())
         .unwrap()
 }
 
-fn server_error_response(_err: std::io::Error) -> http::Response<Body> {
-    http::Response::builder()
-        .status(http::StatusCode::INTERNAL_SERVER_ERROR)
-        .header(http::header::CACHE_CONTROL, "no-store")
-        .body(Body::empty())
-        .unwrap()
+#[cfg(test)]
+mod tests {
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::core::{Embedded, Metadata, headers};
+
+    const FILES: &[&str] = &[
+        "index.html",
+        "app/index.html",
+        "app/main.js",
+        "maintenance.html",
+        "404.html",
+        "docs/404.html",
+    ];
+
+    struct Assets;
+
+    impl Embed for Assets {
+        fn get(path: &str) -> impl Future<Output = std::io::Result<Embedded>> + Send + 'static {
+            let embedded = FILES
+                .iter()
+                .find(|file| **file == path)
+                .map(|file| Embedded {
+                    content: core::Content::from_static(file.as_bytes()),
+                    metadata: Metadata::new(core::content_type(std::path::Path::new(file)))
+                        .etag(headers::ETag::new(file)),
+                })
+                .ok_or_else(|| std::io::ErrorKind::NotFound.into());
+            std::future::ready(embedded)
+        }
+
+        fn nearest_not_found(path: &str) -> Option<Cow<'static, str>> {
+            let mut path = path.trim_matches('/');
+            loop {
+                let page = match path {
+                    "" => "404.html".to_string(),
+                    path => format!("{path}/404.html"),
+                };
+                if FILES.contains(&page.as_str()) {
+                    break Some(Cow::Owned(page));
+                }
+                if path.is_empty() {
+                    break None;
+                }
+                path = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+            }
+        }
+
+        fn etag(content: &[u8], strength: core::hasher::Strength) -> headers::ETag {
+            assert_eq!(strength, core::hasher::Strength::Strong);
+            headers::ETag::new(&format!("{}-bytes", content.len())).unwrap()
+        }
+    }
+
+    /// Assets without files.
+    struct Empty;
+
+    impl Embed for Empty {
+        fn get(_path: &str) -> impl Future<Output = std::io::Result<Embedded>> + Send + 'static {
+            std::future::ready(Err(std::io::ErrorKind::NotFound.into()))
+        }
+    }
+
+    async fn get<E>(service: &ServeEmbed<E>, uri: &str) -> (http::StatusCode, String)
+    where
+        E: Embed + Send + 'static,
+    {
+        let request = http::Request::builder().uri(uri).body(()).unwrap();
+        let response = service.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = match response.headers().get(http::header::LOCATION) {
+            Some(location) => location.to_str().unwrap().to_string(),
+            None => {
+                let content = html::collect(response.into_body()).await.unwrap();
+                String::from_utf8(content.into()).unwrap()
+            }
+        };
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn transformed_page_etag() {
+        let service = ServeEmbed::builder()
+            .html_transform(html::InjectConfig::new("{}"))
+            .build::<Assets>();
+
+        let request = http::Request::builder()
+            .uri("/index.html")
+            .body(())
+            .unwrap();
+        let response = service.clone().oneshot(request).await.unwrap();
+        let length = response.headers()[http::header::CONTENT_LENGTH].clone();
+        let etag = response.headers()[http::header::ETAG].clone();
+        assert_eq!(etag, format!("\"{}-bytes\"", length.to_str().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn transformed_page_not_modified() {
+        let service = ServeEmbed::builder()
+            .html_transform(html::InjectConfig::new("{}"))
+            .security_headers(security::Preset::Strict)
+            .cors(Cors::origins(["https://example.com"]))
+            .build::<Assets>();
+
+        let request = |etag: Option<&http::HeaderValue>| {
+            let mut request = http::Request::builder()
+                .uri("/index.html")
+                .header(http::header::ORIGIN, "https://example.com");
+            if let Some(etag) = etag {
+                request = request.header(http::header::IF_NONE_MATCH, etag);
+            }
+            request.body(()).unwrap()
+        };
+        let response = service.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        let etag = response.headers()[http::header::ETAG].clone();
+
+        let not_modified = service.oneshot(request(Some(&etag))).await.unwrap();
+        assert_eq!(not_modified.status(), http::StatusCode::NOT_MODIFIED);
+        assert!(
+            !not_modified
+                .headers()
+                .contains_key(http::header::CONTENT_LENGTH)
+        );
+        for (name, value) in response.headers() {
+            if name != http::header::CONTENT_LENGTH {
+                assert_eq!(not_modified.headers().get(name), Some(value), "{name}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn nearest_not_found_page() {
+        let service = ServeEmbed::builder()
+            .not_found_service(Assets::nearest_not_found_page())
+            .build::<Assets>();
+
+        let not_found = http::StatusCode::NOT_FOUND;
+        assert_eq!(
+            get(&service, "/docs/guide/missing").await,
+            (not_found, "docs/404.html".into())
+        );
+        assert_eq!(
+            get(&service, "/docs/missing").await,
+            (not_found, "docs/404.html".into())
+        );
+        assert_eq!(
+            get(&service, "/app/missing").await,
+            (not_found, "404.html".into())
+        );
+        assert_eq!(
+            get(&service, "/missing").await,
+            (not_found, "404.html".into())
+        );
+
+        let service = ServeEmbed::builder()
+            .not_found_service(Empty::nearest_not_found_page())
+            .build::<Empty>();
+        assert_eq!(get(&service, "/docs/missing").await, (not_found, "".into()));
+    }
+
+    #[tokio::test]
+    async fn rewrite_missing_assets() {
+        let service = ServeEmbed::builder()
+            .rewrite("/app/*", "/app/index.html")
+            .build::<Assets>();
+
+        let ok = http::StatusCode::OK;
+        let get = |uri| get(&service, uri);
+        assert_eq!(get("/app/main.js").await, (ok, "app/main.js".into()));
+        assert_eq!(get("/app/users/1").await, (ok, "app/index.html".into()));
+        assert_eq!(get("/app/").await, (ok, "app/index.html".into()));
+    }
+
+    #[tokio::test]
+    async fn forced_rules_after_rewrites() {
+        let service = ServeEmbed::builder()
+            .rewrite("/app/*", "/app/index.html")
+            .redirect("/app/*", "/login", http::StatusCode::FOUND)
+            .build::<Assets>();
+
+        let get = |uri| get(&service, uri);
+        assert_eq!(
+            get("/app/main.js?v=1").await,
+            (http::StatusCode::FOUND, "/login?v=1".into())
+        );
+        assert_eq!(
+            get("/app/users/1").await,
+            (http::StatusCode::OK, "app/index.html".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn redirect_preserves_query() {
+        let service = ServeEmbed::builder()
+            .redirect("/old/*", "/:splat", http::StatusCode::MOVED_PERMANENTLY)
+            .redirect("/v1/*", "/app/?v=1", http::StatusCode::FOUND)
+            .build::<Assets>();
+
+        let get = |uri| get(&service, uri);
+        let moved = http::StatusCode::MOVED_PERMANENTLY;
+        assert_eq!(
+            get("/old/app/main.js").await,
+            (moved, "/app/main.js".into())
+        );
+        assert_eq!(
+            get("/old/index.html?lang=en&page=2").await,
+            (moved, "/index.html?lang=en&page=2".into())
+        );
+        assert_eq!(
+            get("/v1/page?lang=en").await,
+            (http::StatusCode::FOUND, "/app/?v=1&lang=en".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn allowed_methods() {
+        let service = ServeEmbed::builder()
+            .cors(Cors::origins(["https://example.com"]).paths("/app/*"))
+            .build::<Assets>();
+
+        let allow = |uri| {
+            let request = http::Request::builder()
+                .method(http::Method::POST)
+                .uri(uri)
+                .body(())
+                .unwrap();
+            let service = service.clone();
+            async move {
+                let response = service.oneshot(request).await.unwrap();
+                assert_eq!(response.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+                response.headers()[http::header::ALLOW].clone()
+            }
+        };
+        assert_eq!(allow("/app/main.js").await, "GET, HEAD, OPTIONS");
+        assert_eq!(allow("/index.html").await, "GET, HEAD");
+    }
 }
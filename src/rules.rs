@@ -0,0 +1,322 @@
+use std::fmt;
+
+use http::StatusCode;
+
+use crate::core::{pattern::Pattern, rules};
+
+/// A rule redirecting or rewriting the requests matching a pattern.
+#[derive(Clone, Debug)]
+pub(crate) struct Rule {
+    pattern: Pattern,
+    target: String,
+    action: Action,
+    /// If false the rule is applied only when no asset is found at the requested path.
+    force: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Action {
+    /// Redirect the client to the target.
+    Redirect(StatusCode),
+    /// Serve the target in place of the requested path, with the status code.
+    Rewrite(StatusCode),
+}
+
+/// The outcome of a matched rule.
+pub(crate) enum Outcome {
+    /// Redirect to the location with the status code.
+    Redirect(StatusCode, String),
+    /// Serve the embedded resource at the path, with the status code.
+    Rewrite(String, StatusCode),
+}
+
+impl Rule {
+    /// Creates a rule redirecting the requests matching the pattern.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid or the status code is not a redirect status code.
+    pub(crate) fn redirect(from: &str, to: &str, status: StatusCode) -> Self {
+        assert!(
+            rules::is_redirect(status.as_u16()),
+            "invalid redirect rule '{from}': {status} is not a redirect status code"
+        );
+        Self::new(from, to, Action::Redirect(status), true)
+    }
+
+    /// Creates a rule rewriting the requests matching the pattern, when no asset is found at the
+    /// requested path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is invalid or the target is not an absolute path.
+    pub(crate) fn rewrite(from: &str, to: &str) -> Self {
+        Self::new(from, to, Action::Rewrite(StatusCode::OK), false)
+    }
+
+    /// Creates a rule from its static definition.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rule is invalid.
+    pub(crate) fn from_static(rule: &rules::RedirectRule) -> Self {
+        let status = StatusCode::from_u16(rule.status).unwrap_or_else(|_| {
+            panic!("invalid rule '{}': invalid status code", rule.from);
+        });
+        let action = if rules::is_redirect(rule.status) {
+            Action::Redirect(status)
+        } else if status.is_redirection() {
+            panic!(
+                "invalid rule '{}': {status} is not a redirect status code",
+                rule.from
+            );
+        } else {
+            Action::Rewrite(status)
+        };
+        Self::new(rule.from, rule.to, action, rule.force)
+    }
+
+    fn new(from: &str, to: &str, action: Action, force: bool) -> Self {
+        let pattern = Pattern::parse(from)
+            .unwrap_or_else(|err| panic!("invalid pattern '{from}' in rule: {err}"));
+        if let Action::Rewrite(_) = action {
+            assert!(
+                to.starts_with('/'),
+                "invalid rewrite rule '{from}': target '{to}' must be an absolute path"
+            );
+        }
+
+        Self {
+            pattern,
+            target: to.to_string(),
+            action,
+            force,
+        }
+    }
+
+    /// Returns true if the rule is applied even when an asset is found at the requested path.
+    pub(crate) fn is_forced(&self) -> bool {
+        self.force
+    }
+
+    /// Applies the rule to the path, if it matches.
+    fn apply(&self, path: &str) -> Option<Outcome> {
+        let captures = self.pattern.matches(path)?;
+        let target = captures.substitute(&self.target);
+        let outcome = match self.action {
+            Action::Redirect(status) => Outcome::Redirect(status, target),
+            Action::Rewrite(status) => Outcome::Rewrite(target, status),
+        };
+        Some(outcome)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self.action {
+            Action::Redirect(status) | Action::Rewrite(status) => status,
+        };
+        let force = if self.force { "!" } else { "" };
+        write!(
+            f,
+            "{} {} {}{force}",
+            self.pattern,
+            self.target,
+            status.as_u16()
+        )
+    }
+}
+
+/// Applies the rules matching the path, in order of evaluation.
+pub(crate) fn apply<'a>(
+    rules: &'a [Rule],
+    path: &'a str,
+) -> impl Iterator<Item = (&'a Rule, Outcome)> + 'a {
+    rules.iter().filter_map(move |rule| {
+        let outcome = rule.apply(path)?;
+        tracing::debug!("Rule '{rule}' matched '{path}'");
+        Some((rule, outcome))
+    })
+}
+
+/// Custom headers added to the responses of the requests matching a pattern.
+#[derive(Clone, Debug)]
+pub(crate) struct HeaderRule {
+    pattern: Pattern,
+    headers: http::HeaderMap,
+}
+
+impl HeaderRule {
+    /// Creates a rule from its static definition.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rule is invalid.
+    pub(crate) fn from_static(rule: &rules::HeaderRule) -> Self {
+        let pattern = Pattern::parse(rule.path)
+            .unwrap_or_else(|err| panic!("invalid pattern '{}' in rule: {err}", rule.path));
+        let headers = rule
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let name = http::HeaderName::from_static(name);
+                let value = http::HeaderValue::from_static(value);
+                (name, value)
+            })
+            .collect();
+
+        Self { pattern, headers }
+    }
+}
+
+/// Adds the custom headers of the rules matching the path to the response.
+///
+/// Headers already in the response are replaced by the custom ones.
+pub(crate) fn apply_headers(rules: &[HeaderRule], path: &str, headers: &mut http::HeaderMap) {
+    let mut custom = http::HeaderMap::new();
+    for rule in rules {
+        if rule.pattern.matches(path).is_some() {
+            for (name, value) in &rule.headers {
+                custom.append(name, value.clone());
+            }
+        }
+    }
+
+    for name in custom.keys() {
+        headers.remove(name);
+        for value in custom.get_all(name) {
+            headers.append(name, value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcomes(rules: &[Rule], path: &str) -> Vec<(bool, String)> {
+        apply(rules, path)
+            .map(|(rule, outcome)| match outcome {
+                Outcome::Redirect(status, location) => {
+                    (rule.is_forced(), format!("{} {location}", status.as_u16()))
+                }
+                Outcome::Rewrite(target, status) => {
+                    (rule.is_forced(), format!("{target} {}", status.as_u16()))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rules_in_order() {
+        let rules = [
+            Rule::redirect("/old/:page", "/new/:page", StatusCode::MOVED_PERMANENTLY),
+            Rule::redirect("/old/*", "/archive/:splat", StatusCode::FOUND),
+            Rule::rewrite("/*", "/index.html"),
+        ];
+        assert_eq!(
+            outcomes(&rules, "/old/page"),
+            [
+                (true, "301 /new/page".to_string()),
+                (true, "302 /archive/page".to_string()),
+                (false, "/index.html 200".to_string()),
+            ]
+        );
+        assert_eq!(
+            outcomes(&rules, "/old/a/b"),
+            [
+                (true, "302 /archive/a/b".to_string()),
+                (false, "/index.html 200".to_string()),
+            ]
+        );
+        assert_eq!(outcomes(&rules[..2], "/other"), []);
+    }
+
+    #[test]
+    fn forced_rules() {
+        let rule = |to: &'static str, status, force| {
+            Rule::from_static(&rules::RedirectRule {
+                from: "/app/*",
+                to,
+                status,
+                force,
+            })
+        };
+        let rules = [
+            rule("/app/index.html", 200, false),
+            rule("/maintenance.html", 503, true),
+            rule("/login", 302, false),
+        ];
+        assert_eq!(
+            outcomes(&rules, "/app/main.js"),
+            [
+                (false, "/app/index.html 200".to_string()),
+                (true, "/maintenance.html 503".to_string()),
+                (false, "302 /login".to_string()),
+            ]
+        );
+        assert!(!Rule::rewrite("/app/*", "/app/index.html").is_forced());
+        assert!(Rule::redirect("/app/*", "/", StatusCode::FOUND).is_forced());
+    }
+
+    #[test]
+    fn substitute_captures() {
+        let rules = [
+            Rule::redirect(
+                "/blog/:year/:slug",
+                "/posts/:slug?year=:year",
+                StatusCode::MOVED_PERMANENTLY,
+            ),
+            Rule::rewrite("/docs/:version/*", "/docs/:splat"),
+        ];
+        assert_eq!(
+            outcomes(&rules, "/blog/2024/hello"),
+            [(true, "301 /posts/hello?year=2024".to_string())]
+        );
+        assert_eq!(
+            outcomes(&rules, "/docs/v1/guide/intro.html"),
+            [(false, "/docs/guide/intro.html 200".to_string())]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must be an absolute path")]
+    fn relative_rewrite_target() {
+        Rule::rewrite("/app/*", "index.html");
+    }
+
+    #[test]
+    fn custom_headers() {
+        let rules = [
+            HeaderRule::from_static(&rules::HeaderRule {
+                path: "/*",
+                headers: &[("x-frame-options", "DENY"), ("link", "</style.css>")],
+            }),
+            HeaderRule::from_static(&rules::HeaderRule {
+                path: "/assets/*",
+                headers: &[
+                    ("cache-control", "public, max-age=31536000"),
+                    ("link", "</font.woff2>"),
+                ],
+            }),
+        ];
+        let mut headers = http::HeaderMap::new();
+        headers.insert("cache-control", "no-cache".parse().unwrap());
+        headers.insert("content-type", "text/css".parse().unwrap());
+        apply_headers(&rules, "/assets/app.css", &mut headers);
+
+        assert_eq!(headers["cache-control"], "public, max-age=31536000");
+        assert_eq!(headers["content-type"], "text/css");
+        assert_eq!(headers["x-frame-options"], "DENY");
+        assert_eq!(
+            headers.get_all("link").iter().collect::<Vec<_>>(),
+            ["</style.css>", "</font.woff2>"]
+        );
+
+        let mut headers = http::HeaderMap::new();
+        headers.insert("cache-control", "no-cache".parse().unwrap());
+        apply_headers(&rules, "/index.html", &mut headers);
+        assert_eq!(headers["cache-control"], "no-cache");
+        assert_eq!(headers["link"], "</style.css>");
+    }
+}
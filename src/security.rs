@@ -0,0 +1,265 @@
+//! Security headers of the responses, set according to their content type.
+
+use http::{HeaderName, HeaderValue, header};
+
+use crate::core::headers::{ContentType, HeaderMapExt};
+
+/// The predefined sets of security headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Disable MIME type sniffing and limit the referrer of all responses. HTML pages are not
+    /// framed and they have a restrictive `Content-Security-Policy`, allowing only the resources
+    /// of the same origin, and a same-origin `Cross-Origin-Opener-Policy`.
+    ///
+    /// The policy blocks the inline scripts and styles, except the script inserted by
+    /// [`InjectConfig`](crate::html::InjectConfig) that extends it.
+    Strict,
+    /// Enable the cross-origin isolation of HTML pages and workers, required to use
+    /// `SharedArrayBuffer`. The responses are loaded only by the same origin, and the pages load
+    /// only the resources of the same origin or the ones allowing it with CORS or
+    /// `Cross-Origin-Resource-Policy`.
+    CrossOriginIsolated,
+}
+
+/// The security headers of the responses, a [`Preset`] or a custom set.
+///
+/// # Example
+///
+/// ```
+/// # use tower_embed::security::{Preset, SecurityHeaders};
+/// let headers = SecurityHeaders::from(Preset::Strict).header_for(
+///     "text/html",
+///     http::HeaderName::from_static("permissions-policy"),
+///     http::HeaderValue::from_static("camera=(), microphone=()"),
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SecurityHeaders {
+    headers: Vec<SecurityHeader>,
+}
+
+#[derive(Clone, Debug)]
+struct SecurityHeader {
+    /// The content types of the responses, `None` for all of them
+    content_type: Option<MediaRange>,
+    name: HeaderName,
+    value: HeaderValue,
+}
+
+/// A media range, like `text/html` or `text/*`.
+#[derive(Clone, Debug)]
+struct MediaRange {
+    type_: String,
+    subtype: Option<String>,
+}
+
+impl MediaRange {
+    fn parse(value: &str) -> Option<Self> {
+        let (type_, subtype) = value.trim().split_once('/')?;
+        let is_token = |value: &str| {
+            !value.is_empty()
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+        };
+        if !is_token(type_) || (subtype != "*" && !is_token(subtype)) {
+            return None;
+        }
+        Some(Self {
+            type_: type_.to_ascii_lowercase(),
+            subtype: (subtype != "*").then(|| subtype.to_ascii_lowercase()),
+        })
+    }
+
+    fn matches(&self, content_type: &ContentType) -> bool {
+        let mime = &content_type.0;
+        mime.type_() == self.type_.as_str()
+            && self
+                .subtype
+                .as_ref()
+                .is_none_or(|subtype| mime.subtype() == subtype.as_str())
+    }
+}
+
+impl SecurityHeaders {
+    /// Create an empty set of security headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the header in all the responses.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.push(SecurityHeader {
+            content_type: None,
+            name,
+            value,
+        });
+        self
+    }
+
+    /// Set the header in the responses with a content type in the media range, like `text/html`
+    /// or `text/*`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the media range is invalid.
+    pub fn header_for(mut self, media_range: &str, name: HeaderName, value: HeaderValue) -> Self {
+        let content_type = MediaRange::parse(media_range)
+            .unwrap_or_else(|| panic!("invalid media range `{media_range}`"));
+        self.headers.push(SecurityHeader {
+            content_type: Some(content_type),
+            name,
+            value,
+        });
+        self
+    }
+
+    /// Add the headers of the other set, after the ones of this set.
+    pub fn with(mut self, other: impl Into<SecurityHeaders>) -> Self {
+        self.headers.extend(other.into().headers);
+        self
+    }
+
+    /// Sets the headers of the response not already set, according to its content type.
+    pub(crate) fn apply(&self, headers: &mut http::HeaderMap) {
+        let content_type = headers.typed_get::<ContentType>();
+        for header in &self.headers {
+            let matches = match (&header.content_type, &content_type) {
+                (None, _) => true,
+                (Some(range), Some(content_type)) => range.matches(content_type),
+                (Some(_), None) => false,
+            };
+            if matches && !headers.contains_key(&header.name) {
+                headers.insert(header.name.clone(), header.value.clone());
+            }
+        }
+    }
+}
+
+impl From<Preset> for SecurityHeaders {
+    fn from(preset: Preset) -> Self {
+        match preset {
+            Preset::Strict => Self::new()
+                .header(
+                    header::X_CONTENT_TYPE_OPTIONS,
+                    HeaderValue::from_static("nosniff"),
+                )
+                .header(
+                    header::REFERRER_POLICY,
+                    HeaderValue::from_static("strict-origin-when-cross-origin"),
+                )
+                .header_for(
+                    "text/html",
+                    header::CONTENT_SECURITY_POLICY,
+                    HeaderValue::from_static(
+                        "default-src 'self'; object-src 'none'; base-uri 'self'; \
+                         frame-ancestors 'none'",
+                    ),
+                )
+                .header_for(
+                    "text/html",
+                    header::X_FRAME_OPTIONS,
+                    HeaderValue::from_static("DENY"),
+                )
+                .header_for(
+                    "text/html",
+                    HeaderName::from_static("cross-origin-opener-policy"),
+                    HeaderValue::from_static("same-origin"),
+                ),
+            Preset::CrossOriginIsolated => {
+                let isolated = ["text/html", "text/javascript", "application/javascript"];
+                isolated
+                    .into_iter()
+                    .fold(Self::new(), |headers, media_range| {
+                        headers
+                            .header_for(
+                                media_range,
+                                HeaderName::from_static("cross-origin-opener-policy"),
+                                HeaderValue::from_static("same-origin"),
+                            )
+                            .header_for(
+                                media_range,
+                                HeaderName::from_static("cross-origin-embedder-policy"),
+                                HeaderValue::from_static("require-corp"),
+                            )
+                    })
+                    .header(
+                        HeaderName::from_static("cross-origin-resource-policy"),
+                        HeaderValue::from_static("same-origin"),
+                    )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_headers(content_type: &str, security: &SecurityHeaders) -> http::HeaderMap {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(content_type).unwrap(),
+        );
+        security.apply(&mut headers);
+        headers
+    }
+
+    #[test]
+    fn headers_by_content_type() {
+        let strict = SecurityHeaders::from(Preset::Strict);
+
+        let headers = response_headers("text/html; charset=utf-8", &strict);
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+        assert_eq!(headers["x-frame-options"], "DENY");
+        assert!(headers.contains_key(header::CONTENT_SECURITY_POLICY));
+
+        let headers = response_headers("image/png", &strict);
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+    }
+
+    #[test]
+    fn combined_presets() {
+        let headers = SecurityHeaders::from(Preset::Strict).with(Preset::CrossOriginIsolated);
+
+        let html = response_headers("text/html", &headers);
+        assert_eq!(html["cross-origin-opener-policy"], "same-origin");
+        assert_eq!(html["cross-origin-embedder-policy"], "require-corp");
+
+        let wasm = response_headers("application/wasm", &headers);
+        assert_eq!(wasm["cross-origin-resource-policy"], "same-origin");
+        assert!(!wasm.contains_key("cross-origin-embedder-policy"));
+    }
+
+    #[test]
+    fn keep_existing_headers() {
+        let security = SecurityHeaders::new().header_for(
+            "text/*",
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'none'"),
+        );
+        let mut headers = http::HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/css"));
+        headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'self'"),
+        );
+        security.apply(&mut headers);
+        assert_eq!(
+            headers[header::CONTENT_SECURITY_POLICY],
+            "default-src 'self'"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid media range `html`")]
+    fn invalid_media_range() {
+        let _ = SecurityHeaders::new().header_for(
+            "html",
+            header::X_FRAME_OPTIONS,
+            HeaderValue::from_static("DENY"),
+        );
+    }
+}
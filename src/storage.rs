@@ -0,0 +1,258 @@
+//! Decompression of the assets stored compressed in the binary.
+
+use std::{
+    collections::HashMap,
+    io,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll, ready},
+};
+
+use bytes::Bytes;
+use futures_core::Stream;
+
+use crate::core::{Content, headers::ContentEncoding};
+
+/// Returns the content decompressed on the fly.
+///
+/// Fails if the feature of the content coding is disabled, the generated code requires it so this
+/// happens only for contents not embedded by the derive macro.
+pub(crate) fn decode(content: Content, encoding: ContentEncoding) -> io::Result<Content> {
+    match encoding {
+        #[cfg(feature = "brotli")]
+        ContentEncoding::Brotli => {
+            let reader = tokio_util::io::StreamReader::new(Frames(content));
+            let decoder = async_compression::tokio::bufread::BrotliDecoder::new(reader);
+            Ok(Content::from_stream(tokio_util::io::ReaderStream::new(
+                decoder,
+            )))
+        }
+        #[cfg(feature = "zstd")]
+        ContentEncoding::Zstd => {
+            let reader = tokio_util::io::StreamReader::new(Frames(content));
+            let decoder = async_compression::tokio::bufread::ZstdDecoder::new(reader);
+            Ok(Content::from_stream(tokio_util::io::ReaderStream::new(
+                decoder,
+            )))
+        }
+        #[allow(unreachable_patterns)]
+        encoding => {
+            let _ = content;
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("decoding of `{}` is not enabled", encoding.as_str()),
+            ))
+        }
+    }
+}
+
+/// A bounded cache of decompressed contents, evicting the least recently used ones.
+pub(crate) struct DecompressionCache {
+    max_bytes: usize,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<String, CacheEntry>,
+    bytes: usize,
+    clock: u64,
+}
+
+struct CacheEntry {
+    content: Bytes,
+    last_used: u64,
+}
+
+impl DecompressionCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            inner: Mutex::default(),
+        }
+    }
+
+    /// Returns the decompressed content, from the cache if present.
+    ///
+    /// The content is buffered to be cached, unless it exceeds the size of the cache.
+    pub async fn decode(
+        &self,
+        key: &str,
+        content: Content,
+        encoding: ContentEncoding,
+    ) -> io::Result<Content> {
+        if let Some(content) = self.get(key) {
+            return Ok(Content::from_bytes(content));
+        }
+
+        let mut decoded = Frames(decode(content, encoding)?);
+        let mut buffer = Vec::new();
+        while let Some(bytes) = std::future::poll_fn(|cx| Pin::new(&mut decoded).poll_next(cx))
+            .await
+            .transpose()?
+        {
+            buffer.extend_from_slice(&bytes);
+            if buffer.len() > self.max_bytes {
+                let prefix = Some(Bytes::from(buffer));
+                return Ok(Content::from_stream(Prefixed { prefix, decoded }));
+            }
+        }
+
+        let content = Bytes::from(buffer);
+        self.insert(key, content.clone());
+        Ok(Content::from_bytes(content))
+    }
+
+    fn get(&self, key: &str) -> Option<Bytes> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+        let entry = inner.entries.get_mut(key)?;
+        entry.last_used = clock;
+        Some(entry.content.clone())
+    }
+
+    fn insert(&self, key: &str, content: Bytes) {
+        let mut inner = self.inner.lock().unwrap();
+        if content.len() > self.max_bytes || inner.entries.contains_key(key) {
+            return;
+        }
+
+        while inner.bytes + content.len() > self.max_bytes {
+            let Some(lru) = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            let entry = inner.entries.remove(&lru).unwrap();
+            inner.bytes -= entry.content.len();
+        }
+
+        inner.clock += 1;
+        inner.bytes += content.len();
+        let entry = CacheEntry {
+            content,
+            last_used: inner.clock,
+        };
+        inner.entries.insert(key.to_string(), entry);
+    }
+}
+
+/// The data frames of a content, as a stream of bytes.
+struct Frames(Content);
+
+impl Stream for Frames {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match ready!(Pin::new(&mut self.0).poll_next(cx)) {
+                Some(Ok(frame)) => {
+                    if let Ok(bytes) = frame.into_data() {
+                        return Poll::Ready(Some(Ok(bytes)));
+                    }
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(io::Error::other(err)))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// The bytes already decompressed followed by the rest of the content.
+struct Prefixed {
+    prefix: Option<Bytes>,
+    decoded: Frames,
+}
+
+impl Stream for Prefixed {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.prefix.take() {
+            Some(prefix) => Poll::Ready(Some(Ok(prefix))),
+            None => Pin::new(&mut self.decoded).poll_next(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Waker;
+
+    use super::*;
+
+    /// Polls a future that is expected to be ready, all the contents are in memory.
+    fn ready<F: Future>(future: F) -> F::Output {
+        let mut cx = Context::from_waker(Waker::noop());
+        match std::pin::pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future is pending"),
+        }
+    }
+
+    fn collect(content: Content) -> Vec<u8> {
+        let mut frames = Frames(content);
+        let mut buffer = Vec::new();
+        while let Some(bytes) = ready(std::future::poll_fn(|cx| {
+            Pin::new(&mut frames).poll_next(cx)
+        })) {
+            buffer.extend_from_slice(&bytes.unwrap());
+        }
+        buffer
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let cache = DecompressionCache::new(8);
+        cache.insert("a", Bytes::from_static(b"aaaa"));
+        cache.insert("b", Bytes::from_static(b"bbbb"));
+        assert!(cache.get("a").is_some());
+
+        cache.insert("c", Bytes::from_static(b"cccc"));
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+
+        cache.insert("d", Bytes::from_static(b"too large"));
+        assert!(cache.get("d").is_none());
+        assert_eq!(cache.inner.lock().unwrap().bytes, 8);
+    }
+
+    #[test]
+    fn prefixed_content() {
+        let decoded = Frames(Content::from_static(b" world"));
+        let prefix = Some(Bytes::from_static(b"hello"));
+        let content = Content::from_stream(Prefixed { prefix, decoded });
+        assert_eq!(collect(content), b"hello world");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn decode_zstd() {
+        // `zstd -19` of "hello hello hello hello"
+        const COMPRESSED: &[u8] = &[
+            0x28, 0xb5, 0x2f, 0xfd, 0x04, 0x68, 0x6d, 0x00, 0x00, 0x38, 0x68, 0x65, 0x6c, 0x6c,
+            0x6f, 0x20, 0x68, 0x01, 0x00, 0x41, 0x8a, 0x11, 0x17, 0x5e, 0xae, 0x0d,
+        ];
+        let content = decode(Content::from_static(COMPRESSED), ContentEncoding::Zstd).unwrap();
+        assert_eq!(collect(content), b"hello hello hello hello");
+
+        let cache = DecompressionCache::new(1024);
+        let content = Content::from_static(COMPRESSED);
+        let content = ready(cache.decode("hello", content, ContentEncoding::Zstd)).unwrap();
+        assert_eq!(collect(content), b"hello hello hello hello");
+        assert!(cache.get("hello").is_some());
+    }
+
+    #[test]
+    fn disabled_encoding() {
+        let content = Content::from_static(b"");
+        if cfg!(not(feature = "brotli")) {
+            assert!(decode(content, ContentEncoding::Brotli).is_err());
+        }
+    }
+}